@@ -9,7 +9,10 @@ use super::daemon::daemon_init;
 use super::display::show_config;
 use super::graph::{get_graph_type, GraphType};
 use super::interactive::interactive;
-use super::interface::{CoreInterface, DaemonControl, DaemonController, Get, Getter, Set, Setter};
+use super::interface::{
+    CoreInterface, DaemonControl, DaemonController, Get, Getter, ProfileControl, ProfileController,
+    Set, Setter,
+};
 use super::settings::Settings;
 use super::setup::check_config_dir_exists;
 use super::warn_user;
@@ -26,6 +29,28 @@ enum DaemonControlType {
     Toggle,
 }
 
+#[derive(StructOpt)]
+enum ProfileType {
+    /// List the profiles in the config, the active one is marked with *
+    #[structopt(name = "list")]
+    List,
+
+    /// Show the active profile and what it changes
+    #[structopt(name = "get")]
+    Get,
+
+    /// Switch the running daemon to a profile, e.g. 'set quiet'
+    #[structopt(name = "set")]
+    Set {
+        #[structopt()]
+        value: String,
+    },
+
+    /// Go back to the config without a profile
+    #[structopt(name = "reset")]
+    Reset,
+}
+
 #[derive(StructOpt)]
 enum GetType {
     /// Get the power
@@ -107,6 +132,28 @@ enum GetType {
         #[structopt(short, long)]
         raw: bool,
     },
+
+    /// The ACPI platform profile and the profiles this system supports
+    #[structopt(name = "platform-profile")]
+    PlatformProfile {
+        #[structopt(short, long)]
+        raw: bool,
+    },
+
+    /// Every attribute of each battery, e.g. model, cycle count and energy
+    #[structopt(name = "battery")]
+    Battery {
+        #[structopt(short, long)]
+        raw: bool,
+
+        /// Output as json
+        #[structopt(short, long)]
+        json: bool,
+
+        /// Show how the battery health changed over time
+        #[structopt(long)]
+        history: bool,
+    },
 }
 
 #[derive(StructOpt)]
@@ -116,6 +163,25 @@ enum SetType {
         #[structopt()]
         value: String,
     },
+
+    /// Set the ACPI platform profile, e.g. 'platform-profile low-power'
+    #[structopt(name = "platform-profile")]
+    PlatformProfile {
+        #[structopt()]
+        value: String,
+    },
+
+    /// Only charge the battery between two percentages, e.g. 'charge-limit 60 80'
+    #[structopt(name = "charge-limit")]
+    ChargeLimit {
+        /// Start charging when the battery falls below this percent
+        #[structopt()]
+        start: u8,
+
+        /// Stop charging when the battery reaches this percent
+        #[structopt()]
+        end: u8,
+    },
 }
 
 #[derive(StructOpt)]
@@ -135,7 +201,6 @@ _
     \
     Automatic CPU frequency scaler and power saver"
 )]
-
 enum ACSCommand {
     /// Controls interaction with a running daemon
     #[structopt(name = "daemon", alias = "d")]
@@ -160,6 +225,21 @@ enum ACSCommand {
         set: SetType,
     },
 
+    /// Switch between the profiles in the config while the daemon runs
+    #[structopt(name = "profile", alias = "p")]
+    Profile {
+        #[structopt(subcommand)]
+        profile: ProfileType,
+    },
+
+    /// Explain why the running daemon is in its current state
+    #[structopt(name = "explain", alias = "why")]
+    Explain {
+        /// Output as json
+        #[structopt(short, long)]
+        json: bool,
+    },
+
     /// Interactive mode for auto clock speed commands
     #[structopt(name = "interactive", alias = "i")]
     Interactive {},
@@ -183,6 +263,10 @@ enum ACSCommand {
         #[structopt(short, long)]
         show_settings: bool,
 
+        /// Run the rules without changing anything, each change is logged instead
+        #[structopt(long = "dry-run")]
+        dry_run: bool,
+
         /// Milliseconds between update (when charging)
         #[structopt(short, long)]
         delay: Option<u64>,
@@ -195,7 +279,7 @@ enum ACSCommand {
         #[structopt(short, long)]
         animation: bool,
 
-        /// Graph "freq", "usage", or "temp"
+        /// Graph "freq", "usage", "temp", or "draw"
         #[structopt(short = "g", long = "--graph")]
         graph_type: Option<String>,
 
@@ -239,7 +323,7 @@ enum ACSCommand {
         #[structopt(long = "--testlog")]
         testlog: bool,
 
-        /// Graph "freq", "usage", or "temp"
+        /// Graph "freq", "usage", "temp", or "draw"
         #[structopt(short = "g", long = "--graph")]
         graph_type: Option<String>,
 
@@ -264,6 +348,7 @@ pub fn parse_args(config: config::Config) {
         set: Set {},
         get: Get {},
         dec: DaemonControl {},
+        pro: ProfileControl {},
     };
 
     match ACSCommand::from_args() {
@@ -286,12 +371,25 @@ pub fn parse_args(config: config::Config) {
             GetType::Temp { raw } => int.get.temp(raw),
             GetType::Govs { raw } => int.get.govs(raw),
             GetType::BatCond { raw } => int.get.bat_cond(raw),
+            GetType::PlatformProfile { raw } => int.get.platform_profile(raw),
+            GetType::Battery { raw, json, history } => int.get.battery(raw, json, history),
         },
 
         ACSCommand::Set { set } => match set {
             SetType::Gov { value } => int.set.gov(value, config, set_settings),
+            SetType::PlatformProfile { value } => int.set.platform_profile(value),
+            SetType::ChargeLimit { start, end } => int.set.charge_limit(start, end),
         },
 
+        ACSCommand::Profile { profile } => match profile {
+            ProfileType::List => int.pro.list(&config),
+            ProfileType::Get => int.pro.get(&config),
+            ProfileType::Set { value } => int.pro.set(value, &config),
+            ProfileType::Reset => int.pro.reset(),
+        },
+
+        ACSCommand::Explain { json } => int.dec.explain(json),
+
         ACSCommand::ShowConfig {} => show_config(&config),
         ACSCommand::InitConfig {} => init_config(),
         ACSCommand::Interactive {} => interactive(),
@@ -307,6 +405,7 @@ pub fn parse_args(config: config::Config) {
             csv_file,
             log_size_cutoff,
             show_settings,
+            dry_run,
         } => {
             check_config_dir_exists();
 
@@ -314,7 +413,7 @@ pub fn parse_args(config: config::Config) {
             if let Some(gt) = graph_type {
                 parsed_graph_type = get_graph_type(&gt);
                 if parsed_graph_type == GraphType::Unknown {
-                    warn_user!("Graph type does not exist! Can be freq, usage, or temp Continuing in 5 seconds...");
+                    warn_user!("Graph type does not exist! Can be freq, usage, temp, or draw Continuing in 5 seconds...");
                     thread::sleep(time::Duration::from_millis(5000));
                 }
             }
@@ -327,6 +426,7 @@ pub fn parse_args(config: config::Config) {
                 delay_battery: effective_delay_battery,
                 delay: regular_delay,
                 edit: true,
+                dry_run,
                 animation,
                 hook: false,
                 graph: parsed_graph_type,
@@ -363,7 +463,7 @@ pub fn parse_args(config: config::Config) {
             if let Some(gt) = graph_type {
                 parsed_graph_type = get_graph_type(&gt);
                 if parsed_graph_type == GraphType::Unknown {
-                    warn_user!("Graph type does not exist! Can be freq, usage, or temp Continuing in 5 seconds...");
+                    warn_user!("Graph type does not exist! Can be freq, usage, temp, or draw Continuing in 5 seconds...");
                     thread::sleep(time::Duration::from_millis(5000));
                 }
             }
@@ -376,6 +476,7 @@ pub fn parse_args(config: config::Config) {
                 delay: regular_delay,
                 delay_battery: effective_delay_battery,
                 edit: false,
+                dry_run: false,
                 hook,
                 animation,
                 graph: parsed_graph_type,
@@ -1,13 +1,22 @@
 #![forbid(unsafe_code)]
 use super::daemon::State;
+use super::hysteresis::Hysteresis;
+use super::power::charge_limit::ChargeThresholds;
+use super::power::ups::UpsConfig;
+use super::predictor::PredictorConfig;
+use super::profiles::Profile;
+use super::rules::{Action, Rule};
 use super::{print_done, warn_user};
+use crate::error::Error;
 use crate::print_error;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::default::Default;
 use std::fmt;
 use std::fs::File;
 use std::io::{ErrorKind, Read, Write};
 use std::path::Path;
+use std::process::exit;
 
 /// Return the local config path
 pub fn config_path() -> String {
@@ -31,14 +40,35 @@ impl Default for Config {
         Config {
             powersave_under: 20,
             overheat_threshold: 80,
+            overheat_action: OverheatAction::Powersave,
             high_cpu_threshold: 50,
             high_cpu_time_needed: 15,
+            idle_minutes: 15,
             active_rules: vec![
                 State::BatteryLow,
                 State::LidClosed,
                 State::Charging,
                 State::CpuUsageHigh,
             ],
+            rule_priority: vec![
+                State::Overheating,
+                State::BatteryLow,
+                State::Idle,
+                State::Charging,
+                State::LidClosed,
+                State::CpuUsageHigh,
+            ],
+            hysteresis: BTreeMap::new(),
+            min_dwell_secs: 0,
+            docked_suppressed_rules: vec![State::LidClosed],
+            charger_min_wattage: 30,
+            charge_thresholds: None,
+            ups: None,
+            platform_profiles: BTreeMap::new(),
+            states: BTreeMap::new(),
+            rules: Vec::new(),
+            profiles: BTreeMap::new(),
+            predictor: None,
         }
     }
 }
@@ -105,32 +135,130 @@ pub fn init_config_file() {
     print_done!("Created config file at '/etc/acs/acs.toml'");
 }
 
-#[derive(Debug, Serialize)]
+/// What to do when the cpu overheats
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OverheatAction {
+    /// Switch to the powersave governor
+    Powersave,
+    /// Cap the frequency of every cpu in kHz
+    FrequencyCap(i32),
+    /// Turn turbo off
+    TurboOff,
+}
+
+#[derive(Clone, Debug, Serialize)]
 pub struct Config {
     /// ACS in edit mode will activate powersave if the battery percentage is under this value
     pub powersave_under: i8,
     /// ACS in edit mode will activate powersave if the temperature reaches this value
     pub overheat_threshold: i8,
+    /// What the overheat_rule does, the other options keep the governor of the rule underneath
+    pub overheat_action: OverheatAction,
     pub high_cpu_threshold: i8,
     pub high_cpu_time_needed: u64,
+    /// ACS in edit mode will activate powersave after this many minutes without keyboard or mouse
+    /// input
+    pub idle_minutes: u64,
+    /// The rules that can be turned off, the overheat_rule is always on
     pub active_rules: Vec<State>,
+    /// When several rules apply, the first one in this list wins
+    ///
+    /// Safety critical rules such as overheating always win and must come first
+    pub rule_priority: Vec<State>,
+    /// Exit thresholds and the time needed to enter and leave each rule, by rule name
+    pub hysteresis: BTreeMap<String, Hysteresis>,
+    /// The least time between two state changes, changes to safety critical states are never held
+    /// back
+    pub min_dwell_secs: u64,
+    /// Rules that do not apply while an external display or dock is connected
+    pub docked_suppressed_rules: Vec<State>,
+    /// Adapters below this wattage are treated like running on battery by the charging rule,
+    /// adapters that do not report their wattage are always treated as full power
+    pub charger_min_wattage: u32,
+    /// Battery charge thresholds applied when ACS starts in edit mode
+    pub charge_thresholds: Option<ChargeThresholds>,
+    /// Where to find a UPS managed by NUT, UPS entries in sysfs are always used
+    pub ups: Option<UpsConfig>,
+    /// The platform profile to use for each state, e.g. battery_percent_rule = "low-power"
+    pub platform_profiles: BTreeMap<String, String>,
+    /// What to set for each state instead of only the governor, e.g.
+    /// [states.ac_charging_rule] governor = "schedutil", ac_full_rule and ac_held_rule fall back
+    /// to ac_charging_rule
+    pub states: BTreeMap<String, Action>,
+    /// User defined rules, these take precedence over the built in rules
+    pub rules: Vec<Rule>,
+    /// Named profiles that change thresholds and states, switched with acs profile set
+    pub profiles: BTreeMap<String, Profile>,
+    /// Apply the cpu usage rule before a burst that the usage history says is likely
+    pub predictor: Option<PredictorConfig>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct SafeConfig {
     pub powersave_under: Option<i8>,
     pub overheat_threshold: Option<i8>,
+    pub overheat_action: Option<OverheatAction>,
     pub high_cpu_threshold: Option<i8>,
     pub high_cpu_time_needed: Option<u64>,
+    pub idle_minutes: Option<u64>,
     pub active_rules: Option<Vec<String>>,
+    pub rule_priority: Option<Vec<String>>,
+    pub hysteresis: Option<BTreeMap<String, Hysteresis>>,
+    pub min_dwell_secs: Option<u64>,
+    pub docked_suppressed_rules: Option<Vec<String>>,
+    pub charger_min_wattage: Option<u32>,
+    pub charge_thresholds: Option<ChargeThresholds>,
+    pub ups: Option<UpsConfig>,
+    pub platform_profiles: Option<BTreeMap<String, String>>,
+    pub states: Option<BTreeMap<String, Action>>,
+    pub rules: Option<Vec<Rule>>,
+    pub profiles: Option<BTreeMap<String, Profile>>,
+    pub predictor: Option<PredictorConfig>,
+}
+
+/// Find the rule (State) with the name used in the config
+fn rule_from_name(name: &str) -> Result<State, Error> {
+    match name {
+        "battery_percent_rule" => Ok(State::BatteryLow),
+        "lid_open_rule" => Ok(State::LidClosed),
+        "ac_charging_rule" => Ok(State::Charging),
+        "ac_full_rule" => Ok(State::ChargingFull),
+        "ac_held_rule" => Ok(State::ChargingHeld),
+        "cpu_usage_rule" => Ok(State::CpuUsageHigh),
+        // overheating is what the overheat_rule used to be called
+        "overheat_rule" | "overheating" => Ok(State::Overheating),
+        "idle_rule" => Ok(State::Idle),
+        _ => Err(Error::InvalidValue(format!(
+            "{} is not a rule, the rules are battery_percent_rule, lid_open_rule, \
+             ac_charging_rule, ac_full_rule, ac_held_rule, cpu_usage_rule, overheat_rule and \
+             idle_rule",
+            name
+        ))),
+    }
+}
+
+fn rules_from_names(names: &[String]) -> Result<Vec<State>, Error> {
+    names.iter().map(|name| rule_from_name(name)).collect()
+}
+
+/// A table keyed by rule with overheating renamed to overheat_rule, so that both names work
+fn rule_keys<T: Clone>(table: &BTreeMap<String, T>) -> BTreeMap<String, T> {
+    table
+        .iter()
+        .map(|(name, value)| match name.as_str() {
+            "overheating" => (State::Overheating.rule_name().to_string(), value.clone()),
+            _ => (name.clone(), value.clone()),
+        })
+        .collect()
 }
 
 trait SafeFillConfig {
-    fn safe_fill_config(&mut self) -> Config;
+    fn safe_fill_config(&mut self) -> Result<Config, Error>;
 }
 
 impl SafeFillConfig for SafeConfig {
-    fn safe_fill_config(&mut self) -> Config {
+    fn safe_fill_config(&mut self) -> Result<Config, Error> {
         // This function makes sure the config contains every value from Config,
         // even when this type is SafeConfig
         //
@@ -154,6 +282,10 @@ impl SafeFillConfig for SafeConfig {
             base.overheat_threshold = ot;
         }
 
+        if let Some(oa) = self.overheat_action {
+            base.overheat_action = oa;
+        }
+
         if let Some(hc) = self.high_cpu_threshold {
             base.high_cpu_threshold = hc;
         }
@@ -162,20 +294,239 @@ impl SafeFillConfig for SafeConfig {
             base.high_cpu_time_needed = ht;
         }
 
+        if let Some(im) = self.idle_minutes {
+            base.idle_minutes = im;
+        }
+
         if let Some(ars) = &self.active_rules {
-            base.active_rules.clear();
-            for rule in ars {
-                base.active_rules.push(match rule.as_str() {
-                    "battery_percent_rule" => State::BatteryLow,
-                    "lid_open_rule" => State::LidClosed,
-                    "ac_charging_rule" => State::Charging,
-                    "cpu_usage_rule" => State::CpuUsageHigh,
-                    _ => State::Unknown,
-                });
+            base.active_rules = rules_from_names(ars)?;
+        }
+
+        if let Some(rp) = &self.rule_priority {
+            base.rule_priority = rules_from_names(rp)?;
+        }
+
+        if let Some(hy) = &self.hysteresis {
+            base.hysteresis = rule_keys(hy);
+        }
+
+        if let Some(md) = self.min_dwell_secs {
+            base.min_dwell_secs = md;
+        }
+
+        if let Some(dsr) = &self.docked_suppressed_rules {
+            base.docked_suppressed_rules = rules_from_names(dsr)?;
+        }
+
+        if let Some(cw) = self.charger_min_wattage {
+            base.charger_min_wattage = cw;
+        }
+
+        if let Some(ct) = self.charge_thresholds {
+            base.charge_thresholds = Some(ct);
+        }
+
+        if let Some(ups) = &self.ups {
+            base.ups = Some(ups.clone());
+        }
+
+        if let Some(pp) = &self.platform_profiles {
+            base.platform_profiles = rule_keys(pp);
+        }
+
+        if let Some(states) = &self.states {
+            base.states = rule_keys(states);
+        }
+
+        if let Some(rules) = &self.rules {
+            base.rules = rules.clone();
+        }
+
+        if let Some(profiles) = &self.profiles {
+            base.profiles = profiles.clone();
+            for profile in base.profiles.values_mut() {
+                profile.states = rule_keys(&profile.states);
             }
         }
 
-        base
+        if let Some(predictor) = self.predictor {
+            base.predictor = Some(predictor);
+        }
+
+        Ok(base)
+    }
+}
+
+impl Config {
+    /// The config with a profile from the profiles table applied
+    pub fn with_profile(&self, name: &str) -> Result<Config, Error> {
+        match self.profiles.get(name) {
+            Some(profile) => Ok(profile.apply(self)),
+            None => Err(Error::InvalidValue(format!(
+                "{} is not a profile in the config",
+                name
+            ))),
+        }
+    }
+
+    /// Check the config, along with the config each profile would make
+    pub fn validate(&self) -> Result<(), Error> {
+        self.validate_rules()?;
+
+        for name in self.profiles.keys() {
+            if let Err(Error::InvalidValue(e)) = self.with_profile(name)?.validate_rules() {
+                return Err(Error::InvalidValue(format!("profile {}: {}", name, e)));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that no rule could override a safety critical rule
+    fn validate_rules(&self) -> Result<(), Error> {
+        for (i, rule) in self.rules.iter().enumerate() {
+            rule.validate()?;
+
+            // Rules are found by name, so a name can only mean one rule
+            if rule.name == "normal" || rule.name == "unknown" || rule_from_name(&rule.name).is_ok()
+            {
+                return Err(Error::InvalidValue(format!(
+                    "rule {} has the name of a built in rule or state",
+                    rule.name
+                )));
+            }
+            if self.rules[..i].iter().any(|other| other.name == rule.name) {
+                return Err(Error::InvalidValue(format!(
+                    "there is more than one rule named {}",
+                    rule.name
+                )));
+            }
+        }
+
+        // Full and held are checked by the ac_charging_rule, so only it can be turned off
+        if let Some(rule) = self
+            .active_rules
+            .iter()
+            .chain(&self.docked_suppressed_rules)
+            .find(|rule| rule.rule() != **rule)
+        {
+            return Err(Error::InvalidValue(format!(
+                "{} is part of {}, use {} in active_rules and docked_suppressed_rules",
+                rule.rule_name(),
+                rule.rule().rule_name(),
+                rule.rule().rule_name()
+            )));
+        }
+
+        if let Some(rule) = self
+            .docked_suppressed_rules
+            .iter()
+            .find(|rule| rule.is_safety_critical())
+        {
+            return Err(Error::InvalidValue(format!(
+                "{} is safety critical and can not be suppressed while docked",
+                rule.rule_name()
+            )));
+        }
+
+        if self.idle_minutes == 0 {
+            return Err(Error::InvalidValue(
+                "idle_minutes must be at least 1, remove idle_rule from active_rules to turn it off"
+                    .to_string(),
+            ));
+        }
+
+        if let Some(predictor) = self.predictor {
+            if !(predictor.probability > 0.0 && predictor.probability <= 1.0) {
+                return Err(Error::InvalidValue(
+                    "the probability of the predictor must be above 0 and at most 1".to_string(),
+                ));
+            }
+        }
+
+        // The tables that are keyed by rule also take normal, for when no rule applies
+        for name in self
+            .platform_profiles
+            .keys()
+            .chain(self.states.keys())
+            .chain(self.hysteresis.keys())
+        {
+            if name != "normal" {
+                rule_from_name(name)?;
+            }
+        }
+
+        if let Some(first) = self
+            .rule_priority
+            .iter()
+            .position(|rule| !rule.is_safety_critical())
+        {
+            if let Some(critical) = self.rule_priority[first..]
+                .iter()
+                .find(|rule| rule.is_safety_critical())
+            {
+                return Err(Error::InvalidValue(format!(
+                    "{} is safety critical and must come before {} in rule_priority",
+                    critical.rule_name(),
+                    self.rule_priority[first].rule_name()
+                )));
+            }
+        }
+
+        for (name, hysteresis) in &self.hysteresis {
+            let rule = rule_from_name(name)?;
+            // Safety critical rules apply as soon as their condition holds
+            if rule.is_safety_critical() && hysteresis.enter_secs.is_some_and(|secs| secs > 0) {
+                return Err(Error::InvalidValue(format!(
+                    "{} is safety critical and can not wait enter_secs before applying",
+                    name
+                )));
+            }
+
+            if rule.rule() != rule {
+                return Err(Error::InvalidValue(format!(
+                    "{} is part of {}, set the hysteresis of {}",
+                    name,
+                    rule.rule().rule_name(),
+                    rule.rule().rule_name()
+                )));
+            }
+        }
+
+        for (name, hysteresis) in &self.hysteresis {
+            let exit = match hysteresis.exit_threshold {
+                Some(exit) => exit,
+                None => continue,
+            };
+
+            // Leaving on the wrong side of the enter threshold would flip between states
+            let valid = match rule_from_name(name)? {
+                State::BatteryLow => exit >= self.powersave_under,
+                State::CpuUsageHigh => exit <= self.high_cpu_threshold,
+                State::Overheating => exit <= self.overheat_threshold,
+                State::LidClosed | State::Charging | State::Idle => {
+                    return Err(Error::InvalidValue(format!(
+                        "{} has no threshold, only enter_secs and exit_secs can be set",
+                        name
+                    )))
+                }
+                _ => {
+                    return Err(Error::InvalidValue(format!(
+                        "hysteresis can not be set for {}",
+                        name
+                    )))
+                }
+            };
+
+            if !valid {
+                return Err(Error::InvalidValue(format!(
+                    "the exit_threshold of {} would leave the rule before entering it",
+                    name
+                )));
+            }
+        }
+
+        Ok(())
     }
 }
 
@@ -194,26 +545,21 @@ fn read_as_string(config_file: &mut File) -> String {
     config
 }
 
-fn parse_as_toml(config: String) -> Config {
+fn parse_as_toml(config: String) -> Result<Config, Error> {
+    // A config that does not parse, e.g. with a misspelled rule condition, is refused instead of
+    // quietly leaving out the parts that did not parse
     let mut safe_config: SafeConfig =
-        // Read the config from config string and if it fails, give the base config with undefined
-        // variables so that the defined variables can be swapped in
-        toml::from_str(config.as_str()).unwrap_or(SafeConfig {
-            powersave_under: None,
-            overheat_threshold: None,
-            high_cpu_threshold: None,
-            high_cpu_time_needed: None,
-            active_rules: None,
-        });
+        toml::from_str(config.as_str()).map_err(|e| Error::InvalidValue(e.to_string()))?;
 
     safe_config.safe_fill_config()
 }
 
-pub fn open_config() -> Result<Config, std::io::Error> {
+pub fn open_config() -> Result<Config, Error> {
     let conf_path = config_path();
     let mut config_file: File = File::open(conf_path)?;
     let config_string = read_as_string(&mut config_file);
-    let config_toml = parse_as_toml(config_string);
+    let config_toml = parse_as_toml(config_string)?;
+    config_toml.validate()?;
 
     Ok(config_toml)
 }
@@ -222,6 +568,11 @@ pub fn get_config() -> Config {
     // Config will always exist, default or otherwise
     match open_config() {
         Ok(conf) => conf,
+        // A config with mistakes or that could put the system at risk is never used
+        Err(Error::InvalidValue(e)) => {
+            print_error!(format!("Refusing to load '/etc/acs/acs.toml': {}", e));
+            exit(1);
+        }
         Err(_) => {
             warn_user!("Using default config. Create file '/etc/acs/acs.toml' for custom config or run 'acs initconfig' to setup default config automatically.");
             // Use default config as config
@@ -240,6 +591,19 @@ mod tests {
         assert!(config.powersave_under > 0 && config.powersave_under < 100);
     }
 
+    #[test]
+    fn default_config_round_trip_unit_test() -> Result<(), Error> {
+        // acs initconfig writes the default config, it has to load again
+        let serialized = toml::to_string(&Config::default()).map_err(|_| Error::Parse)?;
+        // parse_as_toml falls back to the defaults on a syntax error, so check the toml first
+        assert!(toml::from_str::<SafeConfig>(&serialized).is_ok());
+        let config = parse_as_toml(serialized)?;
+        assert!(config.validate().is_ok());
+        assert_eq!(config.active_rules, Config::default().active_rules);
+        assert_eq!(config.overheat_action, OverheatAction::Powersave);
+        Ok(())
+    }
+
     #[test]
     fn read_as_string_unit_test() -> Result<(), std::io::Error> {
         let conf_file = "acs.toml";
@@ -251,11 +615,370 @@ mod tests {
     }
 
     #[test]
-    fn parse_as_toml_unit_test() -> Result<(), std::io::Error> {
+    fn parse_as_toml_unit_test() -> Result<(), Error> {
         let conf_file = "acs.toml";
         let conf_str: String = read_as_string(&mut File::open(conf_file)?);
-        let toml = parse_as_toml(conf_str);
+        let toml = parse_as_toml(conf_str)?;
         assert_eq!(toml.powersave_under, 20);
         Ok(())
     }
+
+    #[test]
+    fn parse_charge_thresholds_unit_test() {
+        let config = parse_as_toml(
+            "powersave_under = 20\n[charge_thresholds]\nstart = 60\nend = 80\n".to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            config.charge_thresholds,
+            Some(ChargeThresholds { start: 60, end: 80 })
+        );
+
+        let config = parse_as_toml("powersave_under = 20\n".to_string()).unwrap();
+        assert_eq!(config.charge_thresholds, None);
+    }
+
+    #[test]
+    fn rule_priority_unit_test() {
+        assert!(Config::default().validate().is_ok());
+
+        let config = parse_as_toml(
+            "rule_priority = [ \"overheat_rule\", \"lid_open_rule\", \"ac_charging_rule\" ]\n"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            config.rule_priority,
+            vec![State::Overheating, State::LidClosed, State::Charging]
+        );
+        assert!(config.validate().is_ok());
+
+        // Overheating can be left out, it always wins
+        let config =
+            parse_as_toml("rule_priority = [ \"ac_charging_rule\" ]\n".to_string()).unwrap();
+        assert!(config.validate().is_ok());
+
+        // But it can not be put below another rule
+        let config = parse_as_toml(
+            "rule_priority = [ \"ac_charging_rule\", \"overheat_rule\" ]\n".to_string(),
+        )
+        .unwrap();
+        assert!(config.validate().is_err());
+
+        // Unknown rule names are an error instead of being ignored
+        assert!(parse_as_toml("rule_priority = [ \"no_such_rule\" ]\n".to_string()).is_err());
+        assert!(parse_as_toml("active_rules = [ \"lid_rule\" ]\n".to_string()).is_err());
+    }
+
+    #[test]
+    fn charging_states_unit_test() {
+        // Full and held can be ordered and configured on their own
+        let config = parse_as_toml(
+            "rule_priority = [ \"overheat_rule\", \"ac_full_rule\", \"lid_open_rule\", \"ac_charging_rule\" ]\n[states.ac_held_rule]\ngovernor = \"powersave\"\n[platform_profiles]\nac_full_rule = \"balanced\"\n"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            config.rule_priority,
+            vec![
+                State::Overheating,
+                State::ChargingFull,
+                State::LidClosed,
+                State::Charging
+            ]
+        );
+        assert!(config.validate().is_ok());
+
+        // But only the ac_charging_rule can be turned off
+        let config = parse_as_toml("active_rules = [ \"ac_full_rule\" ]\n".to_string()).unwrap();
+        assert!(config.validate().is_err());
+        let config =
+            parse_as_toml("[hysteresis.ac_held_rule]\nenter_secs = 5\n".to_string()).unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn profiles_unit_test() {
+        let config = parse_as_toml(
+            "powersave_under = 20\n[profiles.quiet]\npowersave_under = 60\nhigh_cpu_threshold = 90\n[profiles.quiet.states.cpu_usage_rule]\nturbo = false\n[profiles.turbo]\nhigh_cpu_threshold = 20\n"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(config.profiles.len(), 2);
+        assert!(config.validate().is_ok());
+
+        let quiet = config.with_profile("quiet").unwrap();
+        assert_eq!(quiet.powersave_under, 60);
+        assert_eq!(quiet.high_cpu_threshold, 90);
+        assert_eq!(quiet.states["cpu_usage_rule"].turbo, Some(false));
+        assert!(config.with_profile("loud").is_err());
+
+        // A profile is checked like the rest of the config
+        let config = parse_as_toml(
+            "overheat_threshold = 80\n[hysteresis.overheat_rule]\nexit_threshold = 75\n[profiles.hot]\noverheat_threshold = 70\n"
+                .to_string(),
+        )
+        .unwrap();
+        assert!(config.validate().is_err());
+
+        let config =
+            parse_as_toml("[profiles.quiet.states.no_such_rule]\nturbo = false\n".to_string())
+                .unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn predictor_unit_test() {
+        assert_eq!(Config::default().predictor, None);
+
+        let config = parse_as_toml("[predictor]\n".to_string()).unwrap();
+        assert_eq!(config.predictor, Some(PredictorConfig::default()));
+        assert!(config.validate().is_ok());
+
+        let config =
+            parse_as_toml("[predictor]\nprobability = 0.9\nmin_samples = 10\n".to_string())
+                .unwrap();
+        assert_eq!(
+            config.predictor,
+            Some(PredictorConfig {
+                probability: 0.9,
+                min_samples: 10,
+            })
+        );
+
+        let config = parse_as_toml("[predictor]\nprobability = 1.5\n".to_string()).unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn idle_rule_unit_test() {
+        let config = parse_as_toml(
+            "idle_minutes = 30\nactive_rules = [ \"idle_rule\" ]\n[hysteresis.idle_rule]\nexit_secs = 5\n"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(config.idle_minutes, 30);
+        assert_eq!(config.active_rules, vec![State::Idle]);
+        assert!(config.validate().is_ok());
+
+        let config = parse_as_toml("idle_minutes = 0\n".to_string()).unwrap();
+        assert!(config.validate().is_err());
+
+        // Idle has no threshold to leave at
+        let config =
+            parse_as_toml("[hysteresis.idle_rule]\nexit_threshold = 5\n".to_string()).unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn hysteresis_unit_test() {
+        let config = parse_as_toml(
+            "overheat_threshold = 80\nmin_dwell_secs = 10\n[hysteresis.overheat_rule]\nexit_threshold = 75\nexit_secs = 30\n[hysteresis.lid_open_rule]\nenter_secs = 2\n"
+                .to_string(),
+        ).unwrap();
+        assert_eq!(config.min_dwell_secs, 10);
+        assert_eq!(
+            config.hysteresis["overheat_rule"],
+            Hysteresis {
+                exit_threshold: Some(75),
+                enter_secs: None,
+                exit_secs: Some(30),
+            }
+        );
+        assert_eq!(config.hysteresis["lid_open_rule"].enter_secs, Some(2));
+        assert!(config.validate().is_ok());
+
+        // Leaving overheating above the temperature it entered at
+        let config = parse_as_toml(
+            "overheat_threshold = 80\n[hysteresis.overheat_rule]\nexit_threshold = 85\n"
+                .to_string(),
+        )
+        .unwrap();
+        assert!(config.validate().is_err());
+
+        let config = parse_as_toml(
+            "powersave_under = 20\n[hysteresis.battery_percent_rule]\nexit_threshold = 25\n"
+                .to_string(),
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+
+        let config =
+            parse_as_toml("[hysteresis.lid_open_rule]\nexit_threshold = 1\n".to_string()).unwrap();
+        assert!(config.validate().is_err());
+
+        // Overheating can not be made to wait before it applies
+        let config =
+            parse_as_toml("[hysteresis.overheat_rule]\nenter_secs = 20\n".to_string()).unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn overheat_rule_unit_test() {
+        let config = parse_as_toml("overheat_threshold = 80\n".to_string()).unwrap();
+        assert_eq!(config.overheat_action, OverheatAction::Powersave);
+
+        let config = parse_as_toml(
+            "overheat_action = \"turbo_off\"\nactive_rules = [ \"overheat_rule\" ]\n".to_string(),
+        )
+        .unwrap();
+        assert_eq!(config.overheat_action, OverheatAction::TurboOff);
+        assert_eq!(config.active_rules, vec![State::Overheating]);
+
+        let config =
+            parse_as_toml("overheat_action = { frequency_cap = 2000000 }\n".to_string()).unwrap();
+        assert_eq!(
+            config.overheat_action,
+            OverheatAction::FrequencyCap(2000000)
+        );
+
+        // Overheating can not be turned off by docking
+        let config = parse_as_toml(
+            "docked_suppressed_rules = [ \"lid_open_rule\", \"overheat_rule\" ]\n".to_string(),
+        )
+        .unwrap();
+        assert!(config.validate().is_err());
+
+        // Tables keyed by rule also check their names
+        let config =
+            parse_as_toml("[platform_profiles]\noverheat = \"quiet\"\n".to_string()).unwrap();
+        assert!(config.validate().is_err());
+
+        // The name from before overheat_rule still works everywhere
+        let config = parse_as_toml(
+            "rule_priority = [ \"overheating\", \"lid_open_rule\" ]\n[platform_profiles]\noverheating = \"quiet\"\n[hysteresis.overheating]\nexit_threshold = 75\n"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            config.rule_priority,
+            vec![State::Overheating, State::LidClosed]
+        );
+        assert_eq!(config.platform_profiles["overheat_rule"], "quiet");
+        assert_eq!(config.hysteresis["overheat_rule"].exit_threshold, Some(75));
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn parse_docked_suppressed_rules_unit_test() {
+        let config = parse_as_toml("powersave_under = 20\n".to_string()).unwrap();
+        assert_eq!(config.docked_suppressed_rules, vec![State::LidClosed]);
+
+        let config = parse_as_toml(
+            "docked_suppressed_rules = [ \"lid_open_rule\", \"battery_percent_rule\" ]\n"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            config.docked_suppressed_rules,
+            vec![State::LidClosed, State::BatteryLow]
+        );
+
+        let config = parse_as_toml("docked_suppressed_rules = []\n".to_string()).unwrap();
+        assert!(config.docked_suppressed_rules.is_empty());
+    }
+
+    #[test]
+    fn parse_platform_profiles_unit_test() {
+        let config = parse_as_toml(
+            "[platform_profiles]\nbattery_percent_rule = \"low-power\"\nac_charging_rule = \"performance\"\n"
+                .to_string(),
+        ).unwrap();
+        assert_eq!(
+            config.platform_profiles.get("battery_percent_rule"),
+            Some(&"low-power".to_string())
+        );
+        assert_eq!(
+            config.platform_profiles.get("ac_charging_rule"),
+            Some(&"performance".to_string())
+        );
+        assert_eq!(config.platform_profiles.get("lid_open_rule"), None);
+    }
+
+    #[test]
+    fn parse_states_unit_test() {
+        let config = parse_as_toml(
+            "[states.ac_charging_rule]\ngovernor = \"schedutil\"\n[states.battery_percent_rule]\ngovernor = \"powersave\"\nmax_freq = 1200000\nturbo = false\nepp = \"power\"\n"
+                .to_string(),
+        ).unwrap();
+        let charging = &config.states["ac_charging_rule"];
+        assert_eq!(charging.governor, Some("schedutil".to_string()));
+        assert_eq!(charging.turbo, None);
+
+        let battery = &config.states["battery_percent_rule"];
+        assert_eq!(battery.max_freq, Some(1200000));
+        assert_eq!(battery.turbo, Some(false));
+        assert_eq!(battery.epp, Some("power".to_string()));
+        assert!(!config.states.contains_key("lid_open_rule"));
+    }
+
+    #[test]
+    fn parse_rules_unit_test() {
+        let config = parse_as_toml(
+            "[[rules]]\nname = \"busy_on_battery\"\npriority = 10\n[rules.conditions]\nac = false\nusage_above = 70\nfor_secs = 30\n[rules.actions]\ngovernor = \"schedutil\"\nturbo = false\n"
+                .to_string(),
+        ).unwrap();
+        assert_eq!(config.rules.len(), 1);
+        let rule = &config.rules[0];
+        assert_eq!(rule.name, "busy_on_battery");
+        assert_eq!(rule.priority, 10);
+        assert_eq!(rule.conditions.ac, Some(false));
+        assert_eq!(rule.conditions.usage_above, Some(70.0));
+        assert_eq!(rule.conditions.for_secs, Some(30));
+        assert_eq!(rule.actions.governor, Some("schedutil".to_string()));
+        assert_eq!(rule.actions.turbo, Some(false));
+        assert_eq!(rule.actions.max_freq, None);
+        assert!(config.validate().is_ok());
+
+        // A single process name as rules were first written, or a list
+        let config = parse_as_toml(
+            "[[rules]]\nname = \"calls\"\n[rules.conditions]\nprocess = \"zoom\"\n[[rules]]\nname = \"builds\"\n[rules.conditions]\nprocesses = [ \"cargo\", \"rustc\" ]\n"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(
+            config.rules[0].conditions.processes,
+            Some(vec!["zoom".to_string()])
+        );
+        assert_eq!(
+            config.rules[1].conditions.processes,
+            Some(vec!["cargo".to_string(), "rustc".to_string()])
+        );
+
+        // A misspelled condition or action would otherwise make the rule apply all the time
+        assert!(parse_as_toml(
+            "[[rules]]\nname = \"calls\"\n[rules.conditions]\nprocesss = \"zoom\"\n".to_string()
+        )
+        .is_err());
+        assert!(parse_as_toml("[states.normal]\ngovenor = \"powersave\"\n".to_string()).is_err());
+
+        // An invalid rule stops the config from loading
+        let config = parse_as_toml(
+            "[[rules]]\nname = \"night\"\n[rules.conditions]\nafter = \"25:00\"\n".to_string(),
+        )
+        .unwrap();
+        assert!(config.validate().is_err());
+
+        // Two rules with the same name, or a rule named after a built in one
+        let config =
+            parse_as_toml("[[rules]]\nname = \"night\"\n[[rules]]\nname = \"night\"\n".to_string())
+                .unwrap();
+        assert!(config.validate().is_err());
+        for name in ["normal", "overheat_rule", "overheating", "idle_rule"] {
+            let config = parse_as_toml(format!("[[rules]]\nname = \"{}\"\n", name)).unwrap();
+            assert!(config.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn parse_ups_unit_test() {
+        let config = parse_as_toml("[ups]\nname = \"lab\"\n".to_string()).unwrap();
+        assert_eq!(
+            config.ups,
+            Some(UpsConfig {
+                address: "127.0.0.1:3493".to_string(),
+                name: Some("lab".to_string()),
+            })
+        );
+    }
 }
@@ -2,11 +2,11 @@
 use efcl::{bold, color, Color};
 use rand::Rng;
 use std::fmt;
-use std::fs::{self, File};
-use std::io::Write;
+use std::fs;
 use std::path::Path;
 
 use crate::proc::ProcStat;
+use crate::sysfs;
 
 use crate::error::Error;
 use crate::gov::Gov;
@@ -102,10 +102,7 @@ impl Speed for CPU {
         }
 
         let path: String = format!("/sys/devices/system/cpu/{}/{}", self.name, sub_path);
-        let mut buffer = File::create(path)?;
-        buffer.write_all(to_write.as_bytes())?;
-
-        Ok(())
+        sysfs::write(Path::new(&path), &to_write)
     }
 
     /// Pull and update some the attributes of the cpu
@@ -301,11 +298,11 @@ mod tests {
         let mut cpu = CPU::default();
 
         let out = cpu.to_csv();
-        assert!(out.contains(",,0,0,0,0,0,0,\n"));
+        assert!(out.contains(",,0,0,0,0,0,0,,,,,\n"));
 
         cpu.cur_temp = 5;
 
         let out = cpu.to_csv();
-        assert!(out.contains(",,0,0,0,0,5,0,\n"));
+        assert!(out.contains(",,0,0,0,0,5,0,,,,,\n"));
     }
 }
@@ -8,7 +8,7 @@
 //! It is the responsibility of the implementation of the CSV logger to call the write and init
 //! methods in order to actually log data.
 //!
-//! csv.rs logs system data the the state of each cpu to a csv file, not to be confused with
+//! csv.rs logs system data the the state of each cpu and battery to a csv file, not to be confused with
 //! logger.rs which write operation logs to the screen during usage.
 
 use std::fs::File;
@@ -19,13 +19,23 @@ use std::{
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
+use crate::power::battery::Battery;
 use crate::settings::Settings;
 use crate::{
     cpu::CPU,
     logger::{self, Interface, Logger},
 };
 
-pub const CSV_HEADER: &str = "epoch,name,number,max_freq,min_freq,cur_freq,cur_temp,cur_usage,gov";
+/// Each row is either a cpu or a battery, columns that do not apply to the row are left empty
+pub const CSV_HEADER: &str = "epoch,name,number,max_freq,min_freq,cur_freq,cur_temp,cur_usage,gov,capacity,power_draw,time_to_empty,time_to_full";
+
+/// Seconds since the unix epoch used to timestamp each row
+fn epoch() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::new(0_u64, 1_u32))
+        .as_secs()
+}
 
 pub struct CSVWriter {
     /// The max amount of file space the csv logger will take up in MB
@@ -60,11 +70,8 @@ impl Writable for CPU {
     /// Format the csv row
     fn to_csv(&self) -> String {
         format!(
-            "{},{},{},{},{},{},{},{},{}\n",
-            SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap_or(Duration::new(0_u64, 1_u32))
-                .as_secs(),
+            "{},{},{},{},{},{},{},{},{},,,,\n",
+            epoch(),
             self.name,
             self.number,
             self.max_freq,
@@ -77,6 +84,23 @@ impl Writable for CPU {
     }
 }
 
+impl Writable for Battery {
+    /// Format the csv row, the time estimates are in seconds and empty when unknown
+    fn to_csv(&self) -> String {
+        format!(
+            "{},{},,,,,,,,{},{},{},{}\n",
+            epoch(),
+            self.name,
+            self.capacity,
+            self.power_draw,
+            self.time_to_empty()
+                .map_or(String::new(), |t| t.as_secs().to_string()),
+            self.time_to_full()
+                .map_or(String::new(), |t| t.as_secs().to_string()),
+        )
+    }
+}
+
 impl Writer for CSVWriter {
     /// Writes out all the cpu data from the daemon to the csv file
     ///
@@ -28,38 +28,64 @@
 //!
 //! When enabled by the user the daemon will log all of the cpu data to a csv file.
 
+use std::collections::BTreeMap;
 use std::convert::TryInto;
 use std::process::exit;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
 use std::sync::{Arc, Mutex};
 use std::time::SystemTime;
 use std::{thread, time};
 
+use chrono::{Datelike, Local, Timelike};
+
 use efcl::{color, Color};
 use nix::unistd::Uid;
 use serde::Serialize;
 
-use crate::config::Config;
+use crate::config::{Config, OverheatAction};
 use crate::cpu::{Speed, CPU};
 use crate::csv::{gen_writer, CSVWriter, Writer};
-use crate::display::{print_battery_status, print_turbo_status};
+use crate::decision::{Decision, DecisionInputs, RuleCheck};
+use crate::display::{print_batteries_status, print_turbo_status};
+use crate::dock::Dock;
 use crate::error::Error;
 use crate::gov::Gov;
 use crate::graph::{Graph, GraphType, Grapher};
+use crate::hysteresis::Debounce;
+use crate::idle::IdleMonitor;
 use crate::logger;
 use crate::logger::{Interface, Severity};
-use crate::network::{hook, listen};
-use crate::power::battery::{has_battery, Battery};
+use crate::network::send::query_one;
+use crate::network::{hook, listen, Packet};
+use crate::platform_profile::PlatformProfile;
+use crate::power::battery::{has_battery, Batteries, BatteryStatus};
+use crate::power::history::record_batteries;
 use crate::power::lid::{Lid, LidRetriever, LidState};
+use crate::power::ups::Ups;
 use crate::power::{Power, PowerRetriever};
-use crate::proc::{parse_proc_file, read_proc_stat_file, ProcStat};
+use crate::predictor::{Prediction, Predictor};
+use crate::proc::{list_processes, parse_proc_file, read_proc_stat_file, ProcStat};
+use crate::profiles::{load_active_profile, save_active_profile};
+use crate::rules::{Action, Capabilities, Rule, RuleEngine, RuleInputs};
 use crate::settings::Settings;
 use crate::setup::{inside_docker_message, inside_wsl_message};
+use crate::sysfs;
 use crate::system::{
     check_available_governors, check_cpu_freq, check_cpu_temperature, check_cpu_usage,
-    get_highest_temp, inside_docker, inside_wsl, list_cpus,
+    check_turbo_enabled, get_highest_temp, inside_docker, inside_wsl, list_cpus,
+    read_cpuinfo_max_freq, read_epp, set_epp, set_turbo_enabled,
 };
 use crate::warn_user;
 
+/// How often the daemon checks if the batteries are due to be recorded in the history
+const HISTORY_CHECK_INTERVAL: time::Duration = time::Duration::from_secs(60 * 60);
+
+/// How often a UPS that could not be read is tried again, upsd that is down blocks each attempt
+const UPS_RETRY_INTERVAL: time::Duration = time::Duration::from_secs(30);
+
+/// How often what the predictor learned is saved
+const PREDICTOR_SAVE_INTERVAL: time::Duration = time::Duration::from_secs(10 * 60);
+
 /// Describes the state of the machine
 ///
 /// - The state is stored in the Daemon
@@ -77,16 +103,74 @@ pub enum State {
     #[serde(rename = "ac_charging_rule")]
     /// The system will be in performance mode unless the battery is low
     Charging,
+    #[serde(rename = "ac_full_rule")]
+    /// Plugged in and the battery is full, part of the ac_charging_rule
+    ChargingFull,
+    #[serde(rename = "ac_held_rule")]
+    /// Plugged in but the battery is not charging because a charge threshold has been reached,
+    /// part of the ac_charging_rule
+    ChargingHeld,
     #[serde(rename = "cpu_usage_rule")]
     /// The cpu usage has been high for a certain amount of time
     /// The cpu will enter performance mode until the usage goes down
     CpuUsageHigh,
+    #[serde(rename = "overheat_rule")]
     /// Cpu temp is too high
     Overheating,
+    #[serde(rename = "idle_rule")]
+    /// Nobody has used the keyboard or mouse for idle_minutes
+    Idle,
     /// We down know what state the system is in
     Unknown,
+    /// A user defined rule from the config, by its name
+    Rule(String),
+}
+
+impl State {
+    /// The name used for this state in the config
+    pub fn rule_name(&self) -> &str {
+        match self {
+            State::Normal => "normal",
+            State::BatteryLow => "battery_percent_rule",
+            State::LidClosed => "lid_open_rule",
+            State::Charging => "ac_charging_rule",
+            State::ChargingFull => "ac_full_rule",
+            State::ChargingHeld => "ac_held_rule",
+            State::CpuUsageHigh => "cpu_usage_rule",
+            State::Overheating => "overheat_rule",
+            State::Idle => "idle_rule",
+            State::Unknown => "unknown",
+            State::Rule(name) => name,
+        }
+    }
+
+    /// The built in rule that checks for this state, full and held are part of the ac_charging_rule
+    pub fn rule(&self) -> State {
+        match self {
+            State::ChargingFull | State::ChargingHeld => State::Charging,
+            state => state.clone(),
+        }
+    }
+
+    /// Safety critical rules can never be overridden by another rule
+    pub fn is_safety_critical(&self) -> bool {
+        matches!(self, State::Overheating)
+    }
 }
 
+/// The built in states that can be configured in the states table of the config
+const CONFIGURABLE_STATES: [State; 9] = [
+    State::Normal,
+    State::BatteryLow,
+    State::LidClosed,
+    State::Charging,
+    State::ChargingFull,
+    State::ChargingHeld,
+    State::CpuUsageHigh,
+    State::Overheating,
+    State::Idle,
+];
+
 /// Returns the expected governor string based on current state
 ///
 /// Switches through each state and returns the specified governor string
@@ -97,9 +181,13 @@ fn get_governor(current_state: &State) -> &'static str {
         State::BatteryLow => "powersave",
         State::LidClosed => "powersave",
         State::Charging => "performance",
+        State::ChargingFull => "performance",
+        State::ChargingHeld => "performance",
         State::CpuUsageHigh => "performance",
         State::Overheating => "powersave",
+        State::Idle => "powersave",
         State::Unknown => "powersave",
+        State::Rule(_) => "powersave",
     }
 }
 
@@ -130,12 +218,42 @@ pub trait Checker {
 
 /// The daemon structure which contains information about the auto clock speed instance
 pub struct Daemon {
-    pub battery: Battery,
+    pub batteries: Batteries,
     pub power: Power,
     pub lid: Lid,
     pub lid_state: LidState,
+    /// A UPS that acts as the battery of a desktop
+    pub ups: Option<Ups>,
+    /// Set while the UPS can not be read, it is not tried again before this time and failures
+    /// are only logged once
+    pub ups_retry_at: Option<SystemTime>,
+    /// A desktop or server without a battery, the battery and lid rules are skipped
+    pub desktop: bool,
+    /// External displays and docks, some rules are suppressed while docked
+    pub dock: Dock,
+    /// When the battery history was last checked for batteries that are due to be recorded
+    pub last_history_check: Option<SystemTime>,
+    /// User defined rules from the config
+    pub rules: RuleEngine,
+    /// When the keyboard or mouse was last used, for the idle rule
+    pub idle: IdleMonitor,
+    /// Learns when cpu usage bursts happen, only when it is turned on in the config
+    pub predictor: Option<Predictor>,
+    /// What the predictor expects right now and how often it has been right
+    pub prediction: Option<String>,
+    pub last_predictor_save: Option<SystemTime>,
+    /// The action that was last applied, only what changed gets applied again
+    pub applied_action: Action,
+    /// Turbo and the energy performance preference as they were when acs started, these are
+    /// restored when a rule that changed them stops applying
+    pub default_turbo: Option<bool>,
+    pub default_epp: Option<String>,
 
     pub config: Config,
+    /// The config as it was loaded, before the active profile was applied
+    pub base_config: Config,
+    /// The active profile from the profiles table of the config
+    pub profile: Option<String>,
     pub settings: Settings,
 
     pub state: State,
@@ -147,9 +265,24 @@ pub struct Daemon {
     pub last_proc: Vec<ProcStat>,
     pub message: String,
     pub charging: bool,
+    /// Wattage of the connected adapter, None when unknown or unplugged
+    pub adapter_wattage: Option<f32>,
     pub charge: i8,
     pub usage: f32,
-    pub last_below_cpu_usage_percent: Option<SystemTime>,
+    /// Whether each built in rule applies, after hysteresis and debouncing
+    pub debounce: BTreeMap<String, Debounce>,
+    /// When the state last changed, for min_dwell_secs
+    pub last_state_change: Option<SystemTime>,
+    /// The state that would apply if no safety critical rule did
+    pub underlying_state: State,
+    /// How each built in rule was checked in this update
+    pub checks: Vec<RuleCheck>,
+    /// Why the daemon is in its state, from the last update in edit mode
+    pub decision: Option<Decision>,
+    /// How many state changes min_dwell_secs has held back
+    pub suppressed_transitions: u64,
+    /// The state that is being held back, so that each held back change is only counted once
+    pub suppressed_state: Option<State>,
     pub graph: String,
     /// Highest temperature seen last update cycle (highest of any cpu core)
     pub temp_max: i8,
@@ -179,6 +312,582 @@ fn make_gov_schedutil(cpu: &mut CPU) -> Result<(), Error> {
     Ok(())
 }
 
+/// Decide if the system is on external power
+///
+/// The AC adapter is trusted first, then the battery status. A system with neither is assumed to
+/// be a desktop that is always plugged in.
+fn on_external_power(power_source: Result<bool, Error>, batteries: &Batteries) -> bool {
+    if let Ok(plugged) = power_source {
+        return plugged;
+    }
+
+    if let Some(plugged) = batteries.on_external_power() {
+        return plugged;
+    }
+
+    batteries.is_empty()
+}
+
+/// If the UPS should be read now, a UPS that could not be read waits until its retry time
+fn ups_due(retry_at: Option<SystemTime>, now: SystemTime) -> bool {
+    retry_at.is_none_or(|at| now >= at)
+}
+
+/// Decide if an adapter provides enough power to be treated as charging
+///
+/// Adapters that do not report their wattage are assumed to be full power
+fn full_power_adapter(adapter_wattage: Option<f32>, min_wattage: u32) -> bool {
+    adapter_wattage.is_none_or(|w| w >= min_wattage as f32)
+}
+
+impl Daemon {
+    /// Build the config from the base config with a profile, None uses the base config as is
+    pub fn apply_profile(&mut self, profile: Option<String>) -> Result<(), Error> {
+        self.config = match &profile {
+            Some(name) => self.base_config.with_profile(name)?,
+            None => self.base_config.clone(),
+        };
+        self.profile = profile;
+        Ok(())
+    }
+
+    /// Log what dry run kept from being written, with the rule or setting that wanted it
+    fn log_skipped_writes(&mut self, reason: &str) {
+        for write in sysfs::take_skipped_writes() {
+            self.logger
+                .log(&format!("Dry run: {} for {}", write, reason), Severity::Log);
+        }
+    }
+
+    /// Switch to a profile and keep it for the next time acs starts
+    pub fn set_profile(&mut self, profile: Option<String>) -> Result<(), Error> {
+        self.apply_profile(profile.clone())?;
+        self.logger.log(
+            &format!(
+                "Profile changed to {}",
+                profile.as_deref().unwrap_or("none")
+            ),
+            Severity::Log,
+        );
+
+        // Tests and dry runs should not write to /var/lib/acs
+        if self.settings.edit && !self.settings.testing && !self.settings.dry_run {
+            if let Err(e) = save_active_profile(profile.as_deref()) {
+                self.logger.log(
+                    &format!("Could not save the active profile: {:?}", e),
+                    Severity::Warning,
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// What to set for a state, a user rule falls back to the normal state for anything it does
+    /// not set
+    fn action_for(&self, state: &State) -> Action {
+        let builtin = |state: &State| {
+            // Full and held fall back to what is set for the ac_charging_rule
+            let rule = state.rule();
+            let configured = [state, &rule]
+                .iter()
+                .filter_map(|s| self.config.states.get(s.rule_name()).cloned())
+                .fold(Action::default(), Action::or);
+            let platform_profile = [state, &rule]
+                .iter()
+                .find_map(|s| self.config.platform_profiles.get(s.rule_name()))
+                .cloned();
+
+            configured.or(Action {
+                governor: Some(get_governor(state).to_string()),
+                platform_profile,
+                ..Default::default()
+            })
+        };
+
+        match state {
+            State::Rule(name) => match self.rules.get(name) {
+                Some(rule) => rule.actions.clone().or(builtin(&State::Normal)),
+                None => builtin(&State::Normal),
+            },
+            State::Overheating => {
+                let cooling = match self.config.overheat_action {
+                    OverheatAction::Powersave => return builtin(state),
+                    OverheatAction::FrequencyCap(max_freq) => Action {
+                        max_freq: Some(max_freq),
+                        ..Default::default()
+                    },
+                    OverheatAction::TurboOff => Action {
+                        turbo: Some(false),
+                        ..Default::default()
+                    },
+                };
+
+                // The states table can still set anything else for overheat_rule
+                let configured = self
+                    .config
+                    .states
+                    .get(state.rule_name())
+                    .cloned()
+                    .unwrap_or_default();
+                configured
+                    .or(cooling)
+                    .or(self.action_for(&self.underlying_state))
+            }
+            _ => builtin(state),
+        }
+    }
+
+    /// Apply the parts of an action that differ from the last action applied
+    fn apply_action(&mut self, action: &Action) -> Result<(), Error> {
+        let last = self.applied_action.clone();
+
+        if let Some(gov) = &action.governor {
+            if last.governor.as_ref() != Some(gov) {
+                self.set_govs(gov.clone())?;
+            }
+        }
+
+        if let Some(profile) = &action.platform_profile {
+            if last.platform_profile.as_ref() != Some(profile) {
+                match PlatformProfile::new().set(profile) {
+                    Ok(_) => self.logger.log(
+                        &format!("Platform profile set to {}", profile),
+                        Severity::Log,
+                    ),
+                    Err(e) => self.logger.log(
+                        &format!("Could not set platform profile {}: {:?}", profile, e),
+                        Severity::Warning,
+                    ),
+                }
+            }
+        }
+
+        // Turbo, epp and max_freq go back to how they were when a rule stops setting them
+        if action.turbo != last.turbo {
+            if let Some(turbo) = action.turbo.or(self.default_turbo) {
+                if let Err(e) = set_turbo_enabled(turbo) {
+                    self.logger.log(
+                        &format!(
+                            "Could not turn turbo {}: {:?}",
+                            if turbo { "on" } else { "off" },
+                            e
+                        ),
+                        Severity::Warning,
+                    );
+                }
+            }
+        }
+
+        if action.epp != last.epp {
+            if let Some(epp) = action.epp.clone().or(self.default_epp.clone()) {
+                if let Err(e) = set_epp(&self.cpus, &epp) {
+                    self.logger.log(
+                        &format!(
+                            "Could not set energy performance preference {}: {:?}",
+                            epp, e
+                        ),
+                        Severity::Warning,
+                    );
+                }
+            }
+        }
+
+        if action.max_freq != last.max_freq {
+            for cpu in self.cpus.iter_mut() {
+                let max = match action.max_freq {
+                    Some(max) => Ok(max),
+                    None => read_cpuinfo_max_freq(cpu),
+                };
+                if let Err(e) = max.and_then(|max| cpu.set_max(max)) {
+                    self.logger.log(
+                        &format!("Could not set max frequency of {}: {:?}", cpu.name, e),
+                        Severity::Warning,
+                    );
+                }
+            }
+        }
+
+        self.applied_action = action.clone();
+        Ok(())
+    }
+
+    /// The threshold a rule stops applying at, the enter threshold unless one is configured
+    fn exit_threshold(&self, rule: &State, enter: i8) -> i8 {
+        self.config
+            .hysteresis
+            .get(rule.rule_name())
+            .and_then(|h| h.exit_threshold)
+            .unwrap_or(enter)
+    }
+
+    /// Check if a built in rule applies, check is given whether the rule applies right now so
+    /// that it can use the exit threshold, and returns if the condition holds along with what it
+    /// compared for the decision
+    fn debounced(
+        &mut self,
+        rule: &State,
+        active: bool,
+        check: impl Fn(bool) -> (bool, String),
+    ) -> bool {
+        let name = rule.rule_name().to_string();
+        if !active {
+            self.debounce.remove(&name);
+            self.checks.push(RuleCheck {
+                name,
+                active: false,
+                ..Default::default()
+            });
+            return false;
+        }
+
+        let hysteresis = self
+            .config
+            .hysteresis
+            .get(&name)
+            .copied()
+            .unwrap_or_default();
+        // The cpu usage rule has always had its own time needed to enter
+        let enter_secs = hysteresis.enter_secs.unwrap_or(match rule {
+            State::CpuUsageHigh => self.config.high_cpu_time_needed,
+            _ => 0,
+        });
+        let exit_secs = hysteresis.exit_secs.unwrap_or(0);
+
+        let now = SystemTime::now();
+        let debounce = self.debounce.entry(name.clone()).or_default();
+        let (holds, detail) = check(debounce.is_applying());
+        let applies = debounce.update(holds, enter_secs, exit_secs, now);
+        self.checks.push(RuleCheck {
+            name,
+            active: true,
+            applies,
+            remaining_secs: debounce.remaining_secs(enter_secs, exit_secs, now),
+            detail,
+        });
+        applies
+    }
+
+    /// Hold back a state change that comes sooner than min_dwell_secs after the last one
+    ///
+    /// Safety critical states are never held back
+    fn dwell(&mut self, state: State) -> State {
+        if state == self.state || state.is_safety_critical() {
+            self.suppressed_state = None;
+            return state;
+        }
+
+        let dwelled = self.last_state_change.is_none_or(|last| {
+            SystemTime::now()
+                .duration_since(last)
+                .unwrap_or_default()
+                .as_secs()
+                >= self.config.min_dwell_secs
+        });
+        if dwelled {
+            self.suppressed_state = None;
+            return state;
+        }
+
+        if self.suppressed_state.as_ref() != Some(&state) {
+            self.suppressed_transitions += 1;
+            self.logger.log(
+                &format!(
+                    "Held back state change {:?} -> {:?} because of min_dwell_secs ({} held back)",
+                    self.state, state, self.suppressed_transitions
+                ),
+                Severity::Log,
+            );
+            self.suppressed_state = Some(state);
+        }
+
+        self.state.clone()
+    }
+
+    /// What the user rules are checked against
+    fn rule_inputs(&self) -> RuleInputs {
+        let now = Local::now();
+        RuleInputs {
+            charge: self.charge,
+            charging: self.charging
+                && full_power_adapter(self.adapter_wattage, self.config.charger_min_wattage),
+            lid: self.lid_state,
+            usage: self.usage,
+            temp: self.temp_max,
+            time: now.time(),
+            weekday: now.weekday(),
+            processes: if self.rules.needs_processes() {
+                list_processes(self.rules.needs_cmdline(), self.rules.needs_cgroup())
+            } else {
+                Vec::new()
+            },
+        }
+    }
+
+    /// Predict a cpu usage burst from the usage history, then learn from the usage right now
+    ///
+    /// Returns the prediction when a burst is likely enough for the cpu usage rule to apply
+    fn predict_cpu_usage(&mut self) -> Option<Prediction> {
+        let config = self.config.predictor?;
+        let busy = self.usage > self.config.high_cpu_threshold.into();
+        let now = Local::now();
+        let minute_of_day = now.hour() * 60 + now.minute();
+        // Kernel threads such as kworker/u16:3 have no cmdline and come and go too often to learn
+        let processes: Vec<String> = list_processes(true, false)
+            .into_iter()
+            .filter(|process| !process.cmdline.is_empty())
+            .map(|process| process.name)
+            .collect();
+
+        let predictor = self.predictor.as_mut()?;
+        // Predict before learning from this update, so that it is a real prediction
+        let prediction = predictor.predict(minute_of_day, &processes, config.min_samples);
+        let likely = prediction
+            .as_ref()
+            .is_some_and(|p| p.probability >= config.probability);
+        if prediction.is_some() {
+            predictor.score(likely, busy);
+        }
+        predictor.observe(minute_of_day, &processes, busy);
+
+        let expected = match &prediction {
+            Some(p) if likely => format!(
+                "cpu_usage_rule from {} ({:.0}% busy)",
+                p.source,
+                p.probability * 100.0
+            ),
+            Some(_) => String::from("no burst"),
+            None => String::from("still learning"),
+        };
+        self.prediction = Some(match predictor.accuracy() {
+            Some(accuracy) => format!(
+                "expects {}, right {:.0}% of {} predictions",
+                expected,
+                accuracy * 100.0,
+                predictor.predictions
+            ),
+            None => format!("expects {}", expected),
+        });
+
+        prediction.filter(|_| likely)
+    }
+
+    /// Save what the predictor learned every so often, so that it lasts through restarts
+    fn save_predictor(&mut self) {
+        let predictor = match &self.predictor {
+            Some(predictor) => predictor,
+            None => return,
+        };
+
+        if let Some(last) = self.last_predictor_save {
+            match SystemTime::now().duration_since(last) {
+                Ok(elapsed) if elapsed < PREDICTOR_SAVE_INTERVAL => return,
+                _ => {}
+            }
+        }
+
+        if let Err(e) = predictor.save() {
+            self.logger.log(
+                &format!("Could not save the predictor: {:?}", e),
+                Severity::Warning,
+            );
+        }
+        self.last_predictor_save = Some(SystemTime::now());
+    }
+
+    /// Record the battery health history, the history file is checked once an hour
+    fn record_history(&mut self) {
+        if self.batteries.is_empty() {
+            return;
+        }
+
+        if let Some(last) = self.last_history_check {
+            match SystemTime::now().duration_since(last) {
+                Ok(elapsed) if elapsed < HISTORY_CHECK_INTERVAL => return,
+                _ => {}
+            }
+        }
+        self.last_history_check = Some(SystemTime::now());
+
+        if let Err(e) = record_batteries(&self.batteries) {
+            self.logger.log(
+                &format!("Could not record battery history: {:?}", e),
+                Severity::Warning,
+            );
+        }
+    }
+
+    /// Read the UPS, when it is on battery the not-charging and battery percent rules use its charge
+    fn update_ups(&mut self) {
+        let ups = match &mut self.ups {
+            Some(ups) => ups,
+            None => return,
+        };
+
+        // The last status is kept until the UPS is tried again
+        let was_on_battery = ups.on_battery;
+        let now = SystemTime::now();
+        if ups_due(self.ups_retry_at, now) {
+            match ups.update() {
+                Ok(_) => self.ups_retry_at = None,
+                Err(e) => {
+                    if self.ups_retry_at.is_none() {
+                        self.logger.log(
+                            &format!(
+                                "Could not read UPS {}, trying again every {}s: {:?}",
+                                ups.name,
+                                UPS_RETRY_INTERVAL.as_secs(),
+                                e
+                            ),
+                            Severity::Warning,
+                        );
+                    }
+                    self.ups_retry_at = Some(now + UPS_RETRY_INTERVAL);
+                }
+            }
+        }
+
+        if ups.on_battery != was_on_battery {
+            self.logger.log(
+                &format!(
+                    "UPS {} is {} at {}%",
+                    ups.name,
+                    if ups.on_battery {
+                        "on battery"
+                    } else {
+                        "back on mains"
+                    },
+                    ups.charge
+                ),
+                Severity::Warning,
+            );
+        }
+
+        if ups.on_battery {
+            self.charging = false;
+        }
+
+        // A laptop battery outlasts the UPS so it is preferred
+        if self.batteries.is_empty() {
+            self.charge = ups.charge;
+        }
+    }
+}
+
+/// The rules that do not apply to a desktop or server
+///
+/// A UPS stands in for the battery, so only the lid rule is skipped when there is one
+fn desktop_skipped_rules(has_ups: bool) -> Vec<State> {
+    if has_ups {
+        vec![State::LidClosed]
+    } else {
+        vec![State::LidClosed, State::BatteryLow, State::Charging]
+    }
+}
+
+/// Remove the states that can not be configured or that the hardware does not support, so that
+/// they do not fail on every state change. Returns why each one was removed.
+fn remove_invalid_states(
+    states: &mut BTreeMap<String, Action>,
+    capabilities: &Capabilities,
+) -> Vec<String> {
+    let mut problems = Vec::<String>::new();
+
+    states.retain(|name, action| {
+        let problem = if !CONFIGURABLE_STATES.iter().any(|s| s.rule_name() == name) {
+            Some(format!(
+                "State {} is not a state that can be configured and will be ignored",
+                name
+            ))
+        } else {
+            action
+                .validate(capabilities)
+                .err()
+                .map(|e| format!("State {} is invalid and will be ignored: {:?}", name, e))
+        };
+
+        match problem {
+            Some(problem) => {
+                problems.push(problem);
+                false
+            }
+            None => true,
+        }
+    });
+
+    problems
+}
+
+/// Choose the state of the rule that wins out of every rule that applies
+///
+/// Safety critical rules always win, then user rules, then the built in rules in the order of the
+/// priority list. Built in rules left out of the list come last.
+fn choose_state(matched: &[State], user_rule: Option<State>, priority: &[State]) -> State {
+    if let Some(state) = matched.iter().find(|s| s.is_safety_critical()) {
+        return state.clone();
+    }
+
+    if let Some(state) = user_rule {
+        return state;
+    }
+
+    // The ac_charging_rule in the list also stands for full and held
+    priority
+        .iter()
+        .find_map(|rule| matched.iter().find(|s| in_priority(rule, s)))
+        .or(matched.first())
+        .cloned()
+        .unwrap_or(State::Normal)
+}
+
+/// If a rule in the priority list stands for a state
+fn in_priority(rule: &State, state: &State) -> bool {
+    rule == state || *rule == state.rule()
+}
+
+/// Why choose_state picked the winner, for the decision
+fn explain_choice(winner: &State, matched: &[State], priority: &[State]) -> String {
+    if winner.is_safety_critical() {
+        return String::from("safety critical, it always wins");
+    }
+
+    if let State::Rule(_) = winner {
+        return String::from(
+            "the highest priority user rule that applies, these come before the built in rules",
+        );
+    }
+
+    if matched.is_empty() {
+        return String::from("no rule applies");
+    }
+
+    if matched.len() == 1 {
+        return String::from("the only rule that applies");
+    }
+
+    if priority.iter().any(|rule| in_priority(rule, winner)) {
+        String::from("first of the rules that apply in rule_priority")
+    } else {
+        String::from("no rule that applies is in rule_priority, so the first one checked wins")
+    }
+}
+
+/// How a user rule was checked, remaining is None when its conditions do not match and otherwise
+/// the seconds left of for_secs
+fn user_rule_check(rule: &Rule, remaining: Option<u64>, applies: bool) -> RuleCheck {
+    RuleCheck {
+        name: rule.name.clone(),
+        active: true,
+        applies,
+        remaining_secs: remaining.filter(|secs| *secs > 0),
+        detail: match remaining {
+            None => String::from("conditions do not match"),
+            Some(secs) if secs > 0 => String::from("matches, waiting for for_secs"),
+            Some(_) if !applies => String::from("matches, a higher priority rule wins"),
+            Some(_) => format!("priority {}", rule.priority),
+        },
+    }
+}
+
 /// Finds the average cpu usage based on a vector of CPUs
 fn calculate_average_usage(cpus: &Vec<CPU>) -> f32 {
     let mut sum = 0.0;
@@ -203,52 +912,193 @@ impl Checker for Daemon {
     /// Returns the wanted `State` of the machine based on a set of rules
     /// See `State` enum for information about the individual state.
     fn run_state_machine(&mut self) -> State {
-        let mut state = State::Normal;
+        // Every rule that applies, the one that wins is chosen by priority at the end
+        let mut matched = Vec::<State>::new();
 
-        if self.config.active_rules.contains(&State::CpuUsageHigh) {
-            if self.usage > self.config.high_cpu_threshold.into()
-                && self.last_below_cpu_usage_percent.is_none()
-            {
-                self.last_below_cpu_usage_percent = Some(SystemTime::now());
-            }
+        let skipped = if self.desktop {
+            desktop_skipped_rules(self.ups.is_some())
+        } else {
+            Vec::new()
+        };
+        let docked = self.dock.is_docked();
+        let active = |rule: &State| {
+            self.config.active_rules.contains(rule)
+                && !skipped.contains(rule)
+                && !(docked && self.config.docked_suppressed_rules.contains(rule))
+        };
+        let cpu_active = active(&State::CpuUsageHigh);
+        let lid_active = active(&State::LidClosed);
+        let charging_active = active(&State::Charging);
+        let battery_active = active(&State::BatteryLow);
+        // Overheating is always checked, overheat_action changes what it does
+        let overheat_active = true;
+        let idle_active = active(&State::Idle);
+
+        // A likely burst makes the cpu usage rule apply without waiting for it
+        let predicted = if cpu_active {
+            self.predict_cpu_usage()
+        } else {
+            None
+        };
 
-            if self.usage <= self.config.high_cpu_threshold.into() {
-                self.last_below_cpu_usage_percent = None;
+        // The thresholds to enter each rule at, and to leave it at while it applies
+        let usage = self.usage;
+        let cpu_enter = self.config.high_cpu_threshold;
+        let cpu_exit = self.exit_threshold(&State::CpuUsageHigh, cpu_enter);
+        let measured = self.debounced(&State::CpuUsageHigh, cpu_active, |applying| {
+            let threshold = if applying { cpu_exit } else { cpu_enter };
+            (
+                usage > threshold.into(),
+                format!("usage {:.1}%, over {}% applies", usage, threshold),
+            )
+        });
+        // The decision shows that the predictor made the rule apply ahead of its timer
+        if let (false, Some(prediction)) = (measured, &predicted) {
+            if let Some(check) = self.checks.last_mut() {
+                check.applies = true;
+                check.remaining_secs = None;
+                check.detail = format!(
+                    "{}, predicted from {} ({:.0}% busy)",
+                    check.detail,
+                    prediction.source,
+                    prediction.probability * 100.0
+                );
             }
+        }
+        if measured || predicted.is_some() {
+            matched.push(State::CpuUsageHigh);
+        }
 
-            if let Some(last) = self.last_below_cpu_usage_percent {
-                if SystemTime::now()
-                    .duration_since(last)
-                    .expect("Could not compare times")
-                    .as_secs()
-                    >= self.config.high_cpu_time_needed
-                {
-                    state = State::CpuUsageHigh;
-                }
-            }
+        let temp = self.temp_max;
+        let temp_enter = self.config.overheat_threshold;
+        let temp_exit = self.exit_threshold(&State::Overheating, temp_enter);
+        if self.debounced(&State::Overheating, overheat_active, |applying| {
+            let threshold = if applying { temp_exit } else { temp_enter };
+            (
+                temp > threshold,
+                format!("temp {}C, over {}C applies", temp, threshold),
+            )
+        }) {
+            matched.push(State::Overheating);
         }
 
-        if self.temp_max > self.config.overheat_threshold {
-            state = State::Overheating;
+        let lid_state = self.lid_state;
+        if self.debounced(&State::LidClosed, lid_active, |_| {
+            (
+                lid_state == LidState::Closed,
+                format!("lid {:?}", lid_state),
+            )
+        }) {
+            matched.push(State::LidClosed);
         }
 
-        if self.config.active_rules.contains(&State::LidClosed)
-            && self.lid_state == LidState::Closed
-        {
-            state = State::LidClosed;
+        // A weak charger is treated like running on battery
+        let plugged_in = self.charging;
+        let charging = self.charging
+            && full_power_adapter(self.adapter_wattage, self.config.charger_min_wattage);
+        if self.debounced(&State::Charging, charging_active, |_| {
+            let detail = match (plugged_in, charging) {
+                (true, true) => "plugged in",
+                (true, false) => "plugged in to a weak charger",
+                _ => "on battery",
+            };
+            (charging, detail.to_string())
+        }) {
+            matched.push(match self.batteries.status {
+                BatteryStatus::Full => State::ChargingFull,
+                BatteryStatus::NotCharging => State::ChargingHeld,
+                _ => State::Charging,
+            });
         }
 
-        if self.config.active_rules.contains(&State::Charging) && self.charging {
-            state = State::Charging;
+        let charge = self.charge;
+        let battery_enter = self.config.powersave_under;
+        let battery_exit = self.exit_threshold(&State::BatteryLow, battery_enter);
+        if self.debounced(&State::BatteryLow, battery_active, |applying| {
+            let threshold = if applying {
+                battery_exit
+            } else {
+                battery_enter
+            };
+            (
+                charge < threshold,
+                format!("battery {}%, under {}% applies", charge, threshold),
+            )
+        }) {
+            matched.push(State::BatteryLow);
         }
 
-        if self.config.active_rules.contains(&State::BatteryLow)
-            && self.charge < self.config.powersave_under
-        {
-            state = State::BatteryLow;
+        let now = SystemTime::now();
+        self.idle.update(now);
+        let idle_secs = self.idle.idle_secs(now);
+        let idle_needed = self.config.idle_minutes * 60;
+        if self.debounced(&State::Idle, idle_active, |_| match idle_secs {
+            Some(secs) => (
+                secs >= idle_needed,
+                format!("idle {}s, {}s applies", secs, idle_needed),
+            ),
+            None => (false, String::from("input can not be watched")),
+        }) {
+            matched.push(State::Idle);
         }
 
-        state
+        // User rules are evaluated every time so that their timers keep running
+        let mut user_rule = None;
+        let mut user_rules = Vec::<RuleCheck>::new();
+        if !self.rules.is_empty() {
+            let inputs = self.rule_inputs();
+            if let Some(rule) = self.rules.evaluate(&inputs, now) {
+                user_rule = Some(State::Rule(rule.name.clone()));
+            }
+
+            for (rule, remaining) in self.rules.remaining_secs(now) {
+                let applies = user_rule
+                    .as_ref()
+                    .is_some_and(|state| state.rule_name() == rule.name);
+                user_rules.push(user_rule_check(rule, remaining, applies));
+            }
+        }
+
+        // The overheat action can keep the governor of the state that would apply otherwise
+        let underneath: Vec<State> = matched
+            .iter()
+            .filter(|s| !s.is_safety_critical())
+            .cloned()
+            .collect();
+        self.underlying_state =
+            choose_state(&underneath, user_rule.clone(), &self.config.rule_priority);
+
+        let winner = choose_state(&matched, user_rule, &self.config.rule_priority);
+
+        self.decision = Some(Decision {
+            time: Local::now().format("%H:%M:%S").to_string(),
+            inputs: DecisionInputs {
+                charge: self.charge,
+                charging: self.charging,
+                lid: format!("{:?}", self.lid_state),
+                usage: self.usage,
+                temp: self.temp_max,
+                idle_secs,
+                docked,
+                profile: self.profile.clone(),
+            },
+            rules: std::mem::take(&mut self.checks),
+            user_rules,
+            matched: matched.iter().map(|s| s.rule_name().to_string()).collect(),
+            winner: winner.rule_name().to_string(),
+            reason: explain_choice(&winner, &matched, &self.config.rule_priority),
+            trigger: match winner {
+                State::Rule(_) => self.rules.trigger().map(|t| t.to_string()),
+                _ => None,
+            },
+            // Filled in once the state is known
+            held_back: None,
+            state: winner.rule_name().to_string(),
+            action: Action::default(),
+            predictor: self.prediction.clone(),
+        });
+
+        winner
     }
 
     /// Things to be done only at the start of auto clock speed daemon
@@ -269,6 +1119,145 @@ impl Checker for Daemon {
 
         self.csv_writer.init(&mut self.logger);
 
+        // Apply the charge thresholds from the config, this only needs to happen once
+        if self.settings.edit {
+            if let Some(thresholds) = self.config.charge_thresholds {
+                match self.batteries.set_charge_thresholds(&thresholds) {
+                    Ok(controls) => {
+                        for control in controls {
+                            self.logger.log(
+                                &format!("Charge limit set to {} using {}", thresholds, control),
+                                Severity::Log,
+                            );
+                        }
+                    }
+                    Err(e) => self.logger.log(
+                        &format!("Could not set charge limit: {:?}", e),
+                        Severity::Warning,
+                    ),
+                }
+                self.log_skipped_writes("charge_thresholds");
+            }
+        }
+
+        // Input is only watched when the idle rule can use it
+        if self.settings.edit && self.config.active_rules.contains(&State::Idle) {
+            match self.idle.start() {
+                Ok(_) => self.logger.log(
+                    &format!(
+                        "Watching {} for the idle rule",
+                        self.idle.source().unwrap_or_default()
+                    ),
+                    Severity::Log,
+                ),
+                Err(e) => self.logger.log(
+                    &format!(
+                        "Could not watch the keyboard and mouse, idle_rule will not apply: {:?}",
+                        e
+                    ),
+                    Severity::Warning,
+                ),
+            }
+        }
+
+        // Pick up what the predictor learned the last time acs ran
+        if self.settings.edit && self.config.predictor.is_some() {
+            let predictor = if self.settings.testing {
+                Ok(Predictor::default())
+            } else {
+                Predictor::load()
+            };
+            self.predictor = Some(predictor.unwrap_or_else(|e| {
+                self.logger.log(
+                    &format!("Could not read the predictor, it starts over: {:?}", e),
+                    Severity::Warning,
+                );
+                Predictor::default()
+            }));
+        }
+
+        // Remember turbo and epp so that they can be restored after a rule changes them
+        self.default_turbo = check_turbo_enabled().ok();
+        self.default_epp = read_epp().ok();
+
+        // Check the states and rules against what the hardware supports, invalid states are
+        // ignored so that they do not fail on every state change
+        let capabilities = Capabilities::read(&self.cpus);
+        let mut problems = remove_invalid_states(&mut self.base_config.states, &capabilities);
+        for (name, profile) in self.base_config.profiles.iter_mut() {
+            for problem in remove_invalid_states(&mut profile.states, &capabilities) {
+                problems.push(format!("Profile {}: {}", name, problem));
+            }
+        }
+
+        // Rules were checked when the config was loaded, only the hardware is left to check
+        self.base_config.rules.retain(|rule| {
+            match rule
+                .validate()
+                .and_then(|_| rule.actions.validate(&capabilities))
+            {
+                Ok(_) => true,
+                Err(e) => {
+                    problems.push(format!(
+                        "Rule {} is invalid and will be ignored: {:?}",
+                        rule.name, e
+                    ));
+                    false
+                }
+            }
+        });
+        self.rules = RuleEngine::new(self.base_config.rules.clone());
+
+        for problem in problems {
+            self.logger.log(&problem, Severity::Warning);
+        }
+
+        // Go back to the profile that was active when acs last ran
+        let profile = match load_active_profile() {
+            Ok(profile) => profile,
+            Err(e) => {
+                self.logger.log(
+                    &format!("Could not read the active profile: {:?}", e),
+                    Severity::Warning,
+                );
+                None
+            }
+        };
+        if let Err(e) = self.apply_profile(profile.clone()) {
+            self.logger.log(
+                &format!("Profile {:?} could not be used: {:?}", profile, e),
+                Severity::Warning,
+            );
+            // The config is still built from the base config without it
+            self.apply_profile(None).ok();
+        }
+
+        // Check the platform profiles in the config before they are needed
+        if !self.config.platform_profiles.is_empty() {
+            let platform_profile = PlatformProfile::new();
+            match platform_profile.choices() {
+                Ok(choices) => {
+                    for (state, profile) in &self.config.platform_profiles {
+                        if !choices.contains(profile) {
+                            self.logger.log(
+                                &format!(
+                                    "Platform profile {} for {} is not one of {}",
+                                    profile,
+                                    state,
+                                    choices.join(", ")
+                                ),
+                                Severity::Warning,
+                            );
+                        }
+                    }
+                }
+                Err(_) => self.logger.log(
+                    "Platform profiles are configured but this system does not support them",
+                    Severity::Warning,
+                ),
+            }
+        }
+
         if inside_wsl() {
             self.logger
                 .log(&inside_wsl_message(), logger::Severity::Warning);
@@ -285,13 +1274,30 @@ impl Checker for Daemon {
         self.update_all()?;
 
         // Update current states
-        self.charging = self.power.read_power_source().unwrap_or(true);
-        self.charge = self.battery.capacity;
+        self.charging = on_external_power(self.power.read_power_source(), &self.batteries);
+        self.adapter_wattage = if self.charging {
+            self.power.read_adapter_wattage()
+        } else {
+            None
+        };
+        self.charge = self.batteries.capacity;
+        self.update_ups();
+        // Tests and dry runs should not write to /var/lib/acs
+        if self.settings.edit && !self.settings.testing && !self.settings.dry_run {
+            self.record_history();
+            self.save_predictor();
+        }
         self.lid_state = self.lid.read_lid_state()?;
+        self.dock = Dock::detect();
         self.usage = calculate_average_usage(&self.cpus) * 100.0;
 
-        self.csv_writer
-            .write(self.cpus.iter().map(|c| c as _), &mut self.logger);
+        self.csv_writer.write(
+            self.cpus
+                .iter()
+                .map(|c| c as _)
+                .chain(self.batteries.iter().map(|b| b as _)),
+            &mut self.logger,
+        );
 
         Ok(())
     }
@@ -309,17 +1315,38 @@ impl Checker for Daemon {
         self.start_loop()?;
 
         if !self.paused {
-            let state = self.run_state_machine();
+            let winner = self.run_state_machine();
+            let state = self.dwell(winner.clone());
 
             // Check if the state has changed since the last time we checked
             if self.state != state {
+                let trigger = match (&state, self.rules.trigger()) {
+                    (State::Rule(_), Some(process)) => format!(", triggered by {}", process),
+                    _ => String::new(),
+                };
                 self.logger.log(
-                    &format!("State changed: {:?} -> {:?}", self.state, state,),
+                    &format!("State changed: {:?} -> {:?}{}", self.state, state, trigger),
                     logger::Severity::Log,
                 );
+                self.last_state_change = Some(SystemTime::now());
+            }
+
+            // Change the cpu governor and anything else the state sets
+            let action = self.action_for(&state);
 
-                // Change the cpu governor based on the state
-                self.set_govs(get_governor(&state).to_string())?;
+            if let Some(decision) = &mut self.decision {
+                if state != winner {
+                    decision.held_back = Some(format!(
+                        "{} is held back by min_dwell_secs",
+                        winner.rule_name()
+                    ));
+                }
+                decision.state = state.rule_name().to_string();
+                decision.action = action.clone();
+            }
+            if action != self.applied_action {
+                self.apply_action(&action)?;
+                self.log_skipped_writes(state.rule_name());
             }
 
             self.state = state;
@@ -332,6 +1359,14 @@ impl Checker for Daemon {
     /// One iteration of auto clock speed in monitor mode
     fn single_monit(&mut self) -> Result<(), Error> {
         self.start_loop()?;
+        // The daemon in edit mode is the one that switches profiles and makes decisions
+        self.profile = load_active_profile().ok().flatten();
+        if self.settings.verbose && !self.settings.testing {
+            self.decision = match query_one("/tmp/acs.sock", Packet::ExplainRequest()) {
+                Ok(Packet::ExplainResponse(decision)) => decision.map(|d| *d),
+                _ => None,
+            };
+        }
         self.end_loop();
         Ok(())
     }
@@ -340,7 +1375,7 @@ impl Checker for Daemon {
     /// Also updates battery
     fn update_all(&mut self) -> Result<(), Error> {
         if self.do_update_battery {
-            match self.battery.update() {
+            match self.batteries.update() {
                 Ok(_) => {}
                 Err(e) => {
                     if !matches!(e, Error::HdwNotFound) {
@@ -385,6 +1420,12 @@ impl Checker for Daemon {
                 .push_back((check_cpu_temperature(&self.cpus) / 1000.0) as f64);
         }
 
+        if self.settings.graph == GraphType::BatteryDraw {
+            self.grapher
+                .vals
+                .push_back(self.batteries.power_draw() as f64);
+        }
+
         Ok(())
     }
 
@@ -392,7 +1433,10 @@ impl Checker for Daemon {
     /// This method of rendering text reduces lag and fixes a flickering problem from before 0.1.8
     /// This section is just a chunk of the text that gets rendered
     fn preprint_render(&mut self) -> String {
-        let message = format!("{}\n", self.message);
+        let message = match &self.profile {
+            Some(profile) => format!("{}Profile: {}\n\n", self.message, profile),
+            None => format!("{}\n", self.message),
+        };
         let title = "Name\tMax\tMin\tFreq\tTemp\tUsage\tGovernor\n";
 
         // Render each line of cpu core
@@ -402,12 +1446,65 @@ impl Checker for Daemon {
         }
 
         // Prints battery percent or N/A if not
-        let battery_status = print_battery_status(&self.battery);
-        let battery_condition = format!("Condition: {}%", self.battery.condition);
+        let battery_status = print_batteries_status(&self.batteries);
+        let mut battery_condition = if !self.desktop {
+            format!("Condition: {}%", self.batteries.condition)
+        } else if self.ups.is_some() {
+            String::from("Desktop mode: lid rules are skipped")
+        } else {
+            String::from("Desktop mode: battery and lid rules are skipped")
+        };
+
+        if self.dock.is_docked() {
+            battery_condition
+                .push_str(&format!(" Docked: {} display(s)", self.dock.displays.len()));
+        }
+
+        if let Some(ups) = &self.ups {
+            battery_condition.push_str(&format!(
+                " UPS: {}% ({})",
+                ups.charge,
+                if ups.on_battery {
+                    "on battery"
+                } else {
+                    "on mains"
+                }
+            ));
+        }
+
+        if let (State::Rule(name), Some(process)) = (&self.state, self.rules.trigger()) {
+            battery_condition.push_str(&format!(" Rule: {} (triggered by {})", name, process));
+        }
+
+        if self.suppressed_transitions > 0 {
+            battery_condition.push_str(&format!(
+                " Held back changes: {}",
+                self.suppressed_transitions
+            ));
+        }
+
+        if let Some(wattage) = self.adapter_wattage {
+            battery_condition.push_str(&format!(" Adapter: {:.0}W", wattage));
+            if !full_power_adapter(self.adapter_wattage, self.config.charger_min_wattage) {
+                battery_condition.push_str(&format!(
+                    " (under {}W, treated as battery)",
+                    self.config.charger_min_wattage
+                ));
+            }
+        }
+
+        let why = match &self.decision {
+            Some(decision) => format!("Why: {}\n", decision.why()),
+            None => String::new(),
+        };
+        let predictor = match self.decision.as_ref().and_then(|d| d.predictor.as_ref()) {
+            Some(predictor) => format!("Predictor: {}\n", predictor),
+            None => String::new(),
+        };
 
         format!(
-            "{}{}{}\n{}\n{}\n",
-            message, title, cpus, battery_status, battery_condition
+            "{}{}{}\n{}\n{}\n{}{}",
+            message, title, cpus, battery_status, battery_condition, why, predictor
         )
     }
 
@@ -459,6 +1556,12 @@ impl Checker for Daemon {
     /// Output the values from each cpu
     fn print(&mut self) {
         let cores = self.cpus.len();
+        // Each battery gets its own line when there is more than one
+        let battery_lines = if self.batteries.batteries.len() > 1 {
+            self.batteries.batteries.len()
+        } else {
+            0
+        };
 
         // Compute graph before screen is cleared
         if self.settings.graph != GraphType::Hidden {
@@ -492,7 +1595,7 @@ impl Checker for Daemon {
         print!("{}", preprint);
 
         print_turbo_status(
-            cores,
+            cores + battery_lines,
             self.settings.animation,
             term_width.into(),
             delay_in_millis,
@@ -537,6 +1640,7 @@ impl Checker for Daemon {
 /// Message at the header of autoclockspeed - rendered before auto clock speed loop starts
 fn format_message(
     edit: bool,
+    dry_run: bool,
     started_as_edit: bool,
     forced_reason: String,
     delay: u64,
@@ -555,7 +1659,9 @@ fn format_message(
     // was forced to switched modes
     format!(
         "Auto Clock Speed daemon has been initialized in {} mode with a delay of {}ms normally and {}ms when on battery{}\n",
-        if edit {
+        if edit && dry_run {
+            color!(Color::YELLOW, "dry run edit")
+        } else if edit {
             color!(Color::RED, "edit")
         } else {
             color!(Color::YELLOW, "monitor")
@@ -571,14 +1677,18 @@ pub fn daemon_init(settings: Settings, config: Config) -> Arc<Mutex<Daemon>> {
     let mut edit = settings.edit;
     let mut forced_reason: String = String::new();
 
-    // Check if the device has a battery, otherwise force it to monitor mode
-    if !has_battery() {
-        edit = false;
-        forced_reason = "the device has no battery".to_string();
+    let ups = Ups::find(config.ups.as_ref());
+
+    // Without a battery acs runs in desktop mode, which skips the battery and lid rules
+    let desktop = !has_battery();
+
+    // Dry run does not write anything, so it does not need root
+    if settings.dry_run {
+        sysfs::set_dry_run(true);
     }
 
     // Check if effective permissions are enough for edit
-    if edit {
+    if edit && !settings.dry_run {
         // If not running as root, tell the user and force to monitor
         if !Uid::effective().is_root() {
             if !settings.testing {
@@ -597,6 +1707,7 @@ pub fn daemon_init(settings: Settings, config: Config) -> Arc<Mutex<Daemon>> {
 
     let message = format_message(
         edit, // Use current edit because settings.edit has not changed
+        settings.dry_run,
         started_as_edit,
         forced_reason,
         settings.delay,
@@ -608,6 +1719,7 @@ pub fn daemon_init(settings: Settings, config: Config) -> Arc<Mutex<Daemon>> {
         delay: settings.delay,
         delay_battery: settings.delay_battery,
         edit, // Use new edit for new settings
+        dry_run: settings.dry_run,
         animation: settings.animation,
         hook: settings.hook,
         graph: settings.graph.clone(),
@@ -627,33 +1739,61 @@ pub fn daemon_init(settings: Settings, config: Config) -> Arc<Mutex<Daemon>> {
     let power = Power::new();
     let lid = Lid::new();
 
+    let batteries = {
+        let batteries = Batteries::new();
+        battery_present = batteries.is_ok();
+        let mut batteries = batteries.unwrap_or_default();
+        // Errors are reported by update_all later on
+        batteries.update().ok();
+        batteries
+    };
+
+    // If no AC power source can be read, fall back to the battery status
+    let charging = {
+        let source = power.read_power_source();
+        ac_present = source.is_ok();
+        on_external_power(source, &batteries)
+    };
+
     // Create a new Daemon
     let mut daemon: Daemon = Daemon {
-        battery: {
-            let battery = Battery::new();
-            battery_present = battery.is_ok();
-            battery.unwrap_or_default()
-        },
+        batteries,
         cpus: Vec::<CPU>::new(),
         last_proc: Vec::<ProcStat>::new(),
         message,
         lid_state: LidState::Unknown,
         lid,
-        // If edit is still true, then there is definitely a bool result to read_power_source
-        // otherwise, there is a real problem, because there should be a power source possible
-        charging: {
-            let source = power.read_power_source();
-            ac_present = source.is_ok();
-            source.unwrap_or(true)
-        },
+        ups,
+        ups_retry_at: None,
+        desktop,
+        dock: Dock::detect(),
+        idle: IdleMonitor::default(),
+        predictor: None,
+        prediction: None,
+        last_predictor_save: None,
+        last_history_check: None,
+        rules: RuleEngine::new(config.rules.clone()),
+        applied_action: Action::default(),
+        default_turbo: None,
+        default_epp: None,
+        charging,
+        adapter_wattage: None,
         power,
         charge: 100,
         usage: 0.0,
         logger: logger::Logger {
             logs: Vec::<logger::Log>::new(),
         },
+        base_config: config.clone(),
+        profile: None,
         config,
-        last_below_cpu_usage_percent: None,
+        debounce: BTreeMap::new(),
+        last_state_change: None,
+        underlying_state: State::Normal,
+        checks: Vec::new(),
+        decision: None,
+        suppressed_transitions: 0,
+        suppressed_state: None,
         graph: String::new(),
         grapher: Graph::new(),
         temp_max: 0,
@@ -690,7 +1830,8 @@ pub fn daemon_init(settings: Settings, config: Config) -> Arc<Mutex<Daemon>> {
     let daemon_mutex = Arc::new(Mutex::new(daemon));
 
     let c_daemon_mutex = Arc::clone(&daemon_mutex);
-    if settings.edit {
+    // A dry run must not take the socket of the daemon that is really running
+    if settings.edit && !settings.dry_run {
         // Listen for acs clients
         listen::listen("/tmp/acs.sock", c_daemon_mutex);
     } else {
@@ -703,6 +1844,22 @@ pub fn daemon_init(settings: Settings, config: Config) -> Arc<Mutex<Daemon>> {
     daemon_mutex
 }
 
+/// Sleep for the timeout, or less when woken up early
+fn wait_or_wake(wake: &Option<Receiver<()>>, timeout: time::Duration) {
+    match wake {
+        Some(receiver) => match receiver.recv_timeout(timeout) {
+            Ok(()) => {
+                // Several events can arrive at once, one update handles all of them
+                while receiver.try_recv().is_ok() {}
+            }
+            // The lid reader has stopped, so nothing can wake the daemon any more
+            Err(RecvTimeoutError::Disconnected) => thread::sleep(timeout),
+            Err(RecvTimeoutError::Timeout) => {}
+        },
+        None => thread::sleep(timeout),
+    }
+}
+
 pub fn run(daemon_mutex: Arc<Mutex<Daemon>>) -> Result<(), Error> {
     // Aquire the lock for a bit
     let mut daemon = daemon_mutex.lock().unwrap();
@@ -733,6 +1890,9 @@ pub fn run(daemon_mutex: Arc<Mutex<Daemon>>) -> Result<(), Error> {
             exit(0);
         }
 
+        // Opening or closing the lid ends the wait so that the lid rule applies right away
+        let wake = daemon.lid.take_wake_receiver();
+
         drop(daemon);
 
         // Choose which mode acs runs in
@@ -746,7 +1906,7 @@ pub fn run(daemon_mutex: Arc<Mutex<Daemon>>) -> Result<(), Error> {
                     daemon.timeout_battery
                 };
                 drop(daemon);
-                thread::sleep(effective_timeout);
+                wait_or_wake(&wake, effective_timeout);
             }
         } else {
             loop {
@@ -758,7 +1918,7 @@ pub fn run(daemon_mutex: Arc<Mutex<Daemon>>) -> Result<(), Error> {
                     daemon.timeout_battery
                 };
                 drop(daemon);
-                thread::sleep(effective_timeout);
+                wait_or_wake(&wake, effective_timeout);
             }
         }
     }
@@ -771,6 +1931,237 @@ mod tests {
     use super::*;
     use crate::settings::DefaultTesting;
 
+    #[test]
+    fn wait_or_wake_unit_test() {
+        let timeout = time::Duration::from_millis(50);
+
+        // A reader that has stopped must not turn the wait into a busy loop
+        let (sender, receiver) = std::sync::mpsc::channel::<()>();
+        drop(sender);
+        let start = time::Instant::now();
+        wait_or_wake(&Some(receiver), timeout);
+        assert!(start.elapsed() >= timeout);
+
+        // A pending event wakes up early
+        let (sender, receiver) = std::sync::mpsc::channel::<()>();
+        sender.send(()).unwrap();
+        let start = time::Instant::now();
+        wait_or_wake(&Some(receiver), time::Duration::from_secs(10));
+        assert!(start.elapsed() < time::Duration::from_secs(10));
+    }
+
+    #[test]
+    fn on_external_power_unit_test() {
+        let mut batteries = Batteries::default();
+
+        // The AC adapter is trusted first
+        assert!(on_external_power(Ok(true), &batteries));
+        assert!(!on_external_power(Ok(false), &batteries));
+
+        // No adapter and no battery is a desktop
+        assert!(on_external_power(Err(Error::HdwNotFound), &batteries));
+
+        // No adapter, so the battery status is used
+        batteries.batteries.push(Default::default());
+        batteries.status = BatteryStatus::Discharging;
+        assert!(!on_external_power(Err(Error::HdwNotFound), &batteries));
+
+        batteries.status = BatteryStatus::NotCharging;
+        assert!(on_external_power(Err(Error::HdwNotFound), &batteries));
+
+        // A battery with an unknown status should not be assumed to be charging
+        batteries.status = BatteryStatus::Unknown;
+        assert!(!on_external_power(Err(Error::HdwNotFound), &batteries));
+    }
+
+    #[test]
+    fn ups_due_unit_test() {
+        let now = SystemTime::now();
+        assert!(ups_due(None, now));
+
+        // A UPS that failed is left alone until its retry time
+        let retry_at = now + UPS_RETRY_INTERVAL;
+        assert!(!ups_due(Some(retry_at), now));
+        assert!(!ups_due(Some(retry_at), now + time::Duration::from_secs(1)));
+        assert!(ups_due(Some(retry_at), retry_at));
+    }
+
+    #[test]
+    fn full_power_adapter_unit_test() {
+        // Unknown wattage, e.g. a regular AC adapter
+        assert!(full_power_adapter(None, 30));
+
+        assert!(full_power_adapter(Some(65.0), 30));
+        assert!(full_power_adapter(Some(30.0), 30));
+        assert!(!full_power_adapter(Some(15.0), 30));
+    }
+
+    #[test]
+    fn remove_invalid_states_unit_test() {
+        let capabilities = Capabilities {
+            governors: vec!["powersave".to_string(), "performance".to_string()],
+            epps: Vec::new(),
+            freq_range: None,
+            turbo: false,
+            platform_profiles: Vec::new(),
+        };
+        let governor = |gov: &str| Action {
+            governor: Some(gov.to_string()),
+            ..Default::default()
+        };
+
+        let mut states = BTreeMap::from([
+            ("normal".to_string(), governor("powersave")),
+            ("idle_rule".to_string(), governor("schedutil")),
+            ("ac_charging_rule".to_string(), governor("performance")),
+            ("no_such_rule".to_string(), governor("powersave")),
+        ]);
+        let problems = remove_invalid_states(&mut states, &capabilities);
+
+        assert_eq!(problems.len(), 2);
+        assert_eq!(
+            states.keys().collect::<Vec<_>>(),
+            vec!["ac_charging_rule", "normal"]
+        );
+    }
+
+    #[test]
+    fn explain_choice_unit_test() {
+        let priority = vec![State::Overheating, State::BatteryLow, State::CpuUsageHigh];
+
+        assert_eq!(
+            explain_choice(&State::Normal, &[], &priority),
+            "no rule applies"
+        );
+        assert_eq!(
+            explain_choice(
+                &State::Overheating,
+                &[State::CpuUsageHigh, State::Overheating],
+                &priority
+            ),
+            "safety critical, it always wins"
+        );
+        assert!(explain_choice(
+            &State::Rule("busy".to_string()),
+            &[State::CpuUsageHigh],
+            &priority
+        )
+        .contains("user rule"));
+        assert_eq!(
+            explain_choice(&State::CpuUsageHigh, &[State::CpuUsageHigh], &priority),
+            "the only rule that applies"
+        );
+        assert_eq!(
+            explain_choice(
+                &State::BatteryLow,
+                &[State::CpuUsageHigh, State::BatteryLow],
+                &priority
+            ),
+            "first of the rules that apply in rule_priority"
+        );
+        assert_eq!(
+            explain_choice(
+                &State::LidClosed,
+                &[State::LidClosed, State::Idle],
+                &priority
+            ),
+            "no rule that applies is in rule_priority, so the first one checked wins"
+        );
+    }
+
+    #[test]
+    fn user_rule_check_unit_test() {
+        let rule = Rule {
+            name: "busy".to_string(),
+            priority: 10,
+            ..Default::default()
+        };
+
+        let check = user_rule_check(&rule, None, false);
+        assert_eq!(check.detail, "conditions do not match");
+
+        // Still waiting for for_secs, even though another rule wins in the meantime
+        let check = user_rule_check(&rule, Some(12), false);
+        assert_eq!(check.remaining_secs, Some(12));
+        assert_eq!(check.detail, "matches, waiting for for_secs");
+
+        let check = user_rule_check(&rule, Some(0), false);
+        assert_eq!(check.remaining_secs, None);
+        assert_eq!(check.detail, "matches, a higher priority rule wins");
+
+        let check = user_rule_check(&rule, Some(0), true);
+        assert!(check.applies);
+        assert_eq!(check.detail, "priority 10");
+    }
+
+    #[test]
+    fn choose_state_unit_test() {
+        let priority = Config::default().rule_priority;
+
+        assert_eq!(choose_state(&[], None, &priority), State::Normal);
+        assert_eq!(
+            choose_state(&[State::CpuUsageHigh, State::BatteryLow], None, &priority),
+            State::BatteryLow
+        );
+        assert_eq!(
+            choose_state(&[State::LidClosed, State::ChargingFull], None, &priority),
+            State::ChargingFull
+        );
+
+        // Overheating wins even when plugged in or when a user rule applies
+        let user_rule = State::Rule("busy".to_string());
+        assert_eq!(
+            choose_state(
+                &[State::Charging, State::Overheating],
+                Some(user_rule.clone()),
+                &priority
+            ),
+            State::Overheating
+        );
+        assert_eq!(
+            choose_state(&[State::Charging], Some(user_rule.clone()), &priority),
+            user_rule
+        );
+
+        // A different order
+        let priority = vec![State::LidClosed, State::Charging];
+        assert_eq!(
+            choose_state(&[State::Charging, State::LidClosed], None, &priority),
+            State::LidClosed
+        );
+        // Rules left out of the list still apply when nothing else does
+        assert_eq!(
+            choose_state(&[State::CpuUsageHigh], None, &priority),
+            State::CpuUsageHigh
+        );
+
+        // Full can come before the lid while charging and held come after it
+        let priority = vec![State::ChargingFull, State::LidClosed, State::Charging];
+        assert_eq!(
+            choose_state(&[State::LidClosed, State::ChargingFull], None, &priority),
+            State::ChargingFull
+        );
+        assert_eq!(
+            choose_state(&[State::LidClosed, State::ChargingHeld], None, &priority),
+            State::LidClosed
+        );
+    }
+
+    #[test]
+    fn desktop_skipped_rules_unit_test() {
+        let skipped = desktop_skipped_rules(false);
+        assert!(skipped.contains(&State::LidClosed));
+        assert!(skipped.contains(&State::BatteryLow));
+        assert!(skipped.contains(&State::Charging));
+        assert!(!skipped.contains(&State::CpuUsageHigh));
+
+        // The UPS takes the place of the battery
+        let skipped = desktop_skipped_rules(true);
+        assert!(skipped.contains(&State::LidClosed));
+        assert!(!skipped.contains(&State::BatteryLow));
+        assert!(!skipped.contains(&State::Charging));
+    }
+
     #[test]
     fn daemon_init_force_to_monit_integration_test() {
         let settings = Settings::default_testing();
@@ -0,0 +1,245 @@
+#![forbid(unsafe_code)]
+//! Why the daemon is in the state it is in
+//!
+//! Every update in edit mode records a Decision with what the rules were given, which rules
+//! matched, which one won and which timers are still running. `acs explain` asks the running
+//! daemon for the last one over /tmp/acs.sock, and the monitor shows a one line summary.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::rules::Action;
+
+/// What the rules were given
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct DecisionInputs {
+    pub charge: i8,
+    pub charging: bool,
+    pub lid: String,
+    pub usage: f32,
+    pub temp: i8,
+    /// Seconds without keyboard or mouse input, None when input can not be watched
+    pub idle_secs: Option<u64>,
+    pub docked: bool,
+    pub profile: Option<String>,
+}
+
+/// How one rule was checked
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct RuleCheck {
+    pub name: String,
+    /// Rules left out of active_rules, skipped on desktops or suppressed while docked
+    pub active: bool,
+    /// If the rule applies after hysteresis and debouncing
+    pub applies: bool,
+    /// Seconds until the rule starts or stops applying, while a timer is running
+    pub remaining_secs: Option<u64>,
+    /// The input and the threshold it was compared to, e.g. usage 72.0% above 50%
+    pub detail: String,
+}
+
+impl RuleCheck {
+    fn status(&self) -> String {
+        match (self.active, self.applies, self.remaining_secs) {
+            (false, _, _) => String::from("inactive"),
+            (true, true, Some(secs)) => format!("applies, stops in {}s", secs),
+            (true, true, None) => String::from("applies"),
+            (true, false, Some(secs)) => format!("applies in {}s", secs),
+            (true, false, None) => String::from("no"),
+        }
+    }
+}
+
+/// One update of the state machine
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Decision {
+    /// Local time of the update
+    pub time: String,
+    pub inputs: DecisionInputs,
+    /// The built in rules
+    pub rules: Vec<RuleCheck>,
+    /// The user rules from the config, in priority order
+    pub user_rules: Vec<RuleCheck>,
+    /// Every rule that applied
+    pub matched: Vec<String>,
+    /// The rule that won out of the matched rules
+    pub winner: String,
+    /// Why the winner was chosen
+    pub reason: String,
+    /// The process that triggered a user rule
+    pub trigger: Option<String>,
+    /// Why the state did not change to the winner, when min_dwell_secs held it back
+    pub held_back: Option<String>,
+    /// The state the daemon ended up in
+    pub state: String,
+    /// What the state sets
+    pub action: Action,
+    /// What the predictor expects and how often it has been right
+    pub predictor: Option<String>,
+}
+
+impl Decision {
+    /// A short summary for the monitor
+    pub fn why(&self) -> String {
+        let mut why = format!("{}: {}", self.state, self.reason);
+        if let Some(trigger) = &self.trigger {
+            why.push_str(&format!(", triggered by {}", trigger));
+        }
+        if let Some(held_back) = &self.held_back {
+            why.push_str(&format!(" ({})", held_back));
+        }
+        why
+    }
+}
+
+/// Only the parts of an action that are set, e.g. governor powersave, turbo off
+fn describe_action(action: &Action) -> String {
+    let mut parts = Vec::<String>::new();
+    if let Some(governor) = &action.governor {
+        parts.push(format!("governor {}", governor));
+    }
+    if let Some(epp) = &action.epp {
+        parts.push(format!("epp {}", epp));
+    }
+    if let Some(max_freq) = action.max_freq {
+        parts.push(format!("max_freq {}kHz", max_freq));
+    }
+    if let Some(turbo) = action.turbo {
+        parts.push(format!("turbo {}", if turbo { "on" } else { "off" }));
+    }
+    if let Some(profile) = &action.platform_profile {
+        parts.push(format!("platform profile {}", profile));
+    }
+
+    if parts.is_empty() {
+        String::from("nothing")
+    } else {
+        parts.join(", ")
+    }
+}
+
+fn write_checks(f: &mut fmt::Formatter, checks: &[RuleCheck]) -> fmt::Result {
+    let width = checks.iter().map(|c| c.name.len()).max().unwrap_or(0);
+    for check in checks {
+        write!(
+            f,
+            "  {:width$}  {}",
+            check.name,
+            check.status(),
+            width = width
+        )?;
+        if check.active && !check.detail.is_empty() {
+            write!(f, " ({})", check.detail)?;
+        }
+        writeln!(f)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "State: {} at {}", self.state, self.time)?;
+        writeln!(f, "Why: {}", self.why())?;
+        writeln!(f, "Sets: {}", describe_action(&self.action))?;
+        if let Some(predictor) = &self.predictor {
+            writeln!(f, "Predictor: {}", predictor)?;
+        }
+
+        let inputs = &self.inputs;
+        write!(
+            f,
+            "Inputs: battery {}%, {}, lid {}, usage {:.1}%, temp {}C",
+            inputs.charge,
+            if inputs.charging {
+                "plugged in"
+            } else {
+                "on battery"
+            },
+            inputs.lid,
+            inputs.usage,
+            inputs.temp
+        )?;
+        if let Some(idle) = inputs.idle_secs {
+            write!(f, ", idle {}s", idle)?;
+        }
+        if inputs.docked {
+            write!(f, ", docked")?;
+        }
+        if let Some(profile) = &inputs.profile {
+            write!(f, ", profile {}", profile)?;
+        }
+        writeln!(f)?;
+
+        writeln!(f, "Rules:")?;
+        write_checks(f, &self.rules)?;
+        if !self.user_rules.is_empty() {
+            writeln!(f, "User rules:")?;
+            write_checks(f, &self.user_rules)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decision_display_unit_test() {
+        let decision = Decision {
+            time: "12:00:00".to_string(),
+            inputs: DecisionInputs {
+                charge: 54,
+                lid: "Open".to_string(),
+                usage: 72.0,
+                temp: 61,
+                ..Default::default()
+            },
+            rules: vec![
+                RuleCheck {
+                    name: "cpu_usage_rule".to_string(),
+                    active: true,
+                    applies: true,
+                    remaining_secs: None,
+                    detail: "usage 72.0% above 50%".to_string(),
+                },
+                RuleCheck {
+                    name: "idle_rule".to_string(),
+                    active: true,
+                    applies: false,
+                    remaining_secs: Some(30),
+                    detail: String::new(),
+                },
+                RuleCheck {
+                    name: "lid_open_rule".to_string(),
+                    active: false,
+                    ..Default::default()
+                },
+            ],
+            matched: vec!["cpu_usage_rule".to_string()],
+            winner: "cpu_usage_rule".to_string(),
+            reason: "the only rule that applies".to_string(),
+            state: "cpu_usage_rule".to_string(),
+            action: Action {
+                governor: Some("performance".to_string()),
+                turbo: Some(false),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(decision.why(), "cpu_usage_rule: the only rule that applies");
+        assert_eq!(
+            decision.to_string(),
+            "State: cpu_usage_rule at 12:00:00
+Why: cpu_usage_rule: the only rule that applies
+Sets: governor performance, turbo off
+Inputs: battery 54%, on battery, lid Open, usage 72.0%, temp 61C
+Rules:
+  cpu_usage_rule  applies (usage 72.0% above 50%)
+  idle_rule       applies in 30s
+  lid_open_rule   inactive
+"
+        );
+    }
+}
@@ -1,13 +1,17 @@
 #![forbid(unsafe_code)]
+use chrono::{DateTime, Local};
 use efcl::{color, Color};
 use std::fmt::Display;
 use std::thread;
+use std::time::{Duration, UNIX_EPOCH};
 
 use super::config::Config;
 use super::cpu::CPU;
+use super::power::history::{wear_rate_per_month, BatteryHistory};
 use super::power::lid::LidState;
+use super::power::PowerSupply;
 use super::system::check_turbo_enabled;
-use crate::power::battery::{has_battery, Battery, BatteryStatus};
+use crate::power::battery::{has_battery, Batteries, Battery, BatteryStatus};
 
 #[macro_export]
 macro_rules! warn_user {
@@ -55,14 +59,102 @@ pub fn print_freq(f: f32, raw: bool) {
     }
 }
 
-pub fn print_power(lid: LidState, bat: i8, plugged: bool, raw: bool) {
+pub fn print_power(
+    lid: LidState,
+    batteries: &Batteries,
+    supplies: &[PowerSupply],
+    plugged: bool,
+    raw: bool,
+) {
     if raw {
-        println!("{} {} {}", lid, bat, plugged);
+        println!("{} {} {}", lid, batteries.capacity, plugged);
+        for supply in supplies.iter().filter(|s| s.is_external()) {
+            println!(
+                "{} {} {} {:.0}",
+                supply.name,
+                supply.supply_type,
+                supply.online,
+                supply.wattage.unwrap_or(0.0)
+            );
+        }
+        for battery in batteries.iter() {
+            println!(
+                "{} {} {} {:.2} {} {}",
+                battery.name,
+                battery.capacity,
+                battery.status,
+                battery.power_draw,
+                battery.time_to_empty().map_or(0, |t| t.as_secs()),
+                battery.time_to_full().map_or(0, |t| t.as_secs()),
+            );
+        }
     } else {
-        println!("Lid: {} Battery: {} Plugged: {}", lid, bat, plugged);
+        println!(
+            "Lid: {} Battery: {} ({}) Plugged: {}{}",
+            lid,
+            batteries.capacity,
+            batteries.status,
+            plugged,
+            format_power_draw(
+                batteries.power_draw(),
+                batteries.time_to_empty(),
+                batteries.time_to_full()
+            )
+        );
+        for battery in batteries.iter() {
+            println!(
+                "  {}: {}% ({}){}",
+                battery.name,
+                battery.capacity,
+                battery.status,
+                format_power_draw(
+                    battery.power_draw,
+                    battery.time_to_empty(),
+                    battery.time_to_full()
+                )
+            );
+        }
+        for supply in supplies.iter().filter(|s| s.is_external()) {
+            println!(
+                "  {}: {} ({}){}",
+                supply.name,
+                supply.supply_type,
+                if supply.online { "online" } else { "offline" },
+                supply
+                    .wattage
+                    .map_or(String::new(), |w| format!(" {:.0}W", w))
+            );
+        }
     }
 }
 
+/// Format a duration as hours and minutes, e.g. 2h 05m
+pub fn format_duration(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
+
+/// Format the power draw of a battery along with how long until it is empty or full
+pub fn format_power_draw(
+    power_draw: f32,
+    time_to_empty: Option<Duration>,
+    time_to_full: Option<Duration>,
+) -> String {
+    if power_draw <= 0.0 {
+        return String::new();
+    }
+
+    let estimate = if let Some(t) = time_to_empty {
+        format!(", {} until empty", format_duration(t))
+    } else if let Some(t) = time_to_full {
+        format!(", {} until full", format_duration(t))
+    } else {
+        String::new()
+    };
+
+    format!(" Draw: {:.2}W{}", power_draw, estimate)
+}
+
 pub fn print_bat_cond(c: i8, raw: bool) {
     if raw {
         println!("{}", c);
@@ -71,6 +163,96 @@ pub fn print_bat_cond(c: i8, raw: bool) {
     }
 }
 
+pub fn print_batteries(batteries: &Batteries, raw: bool, json: bool) {
+    if json {
+        match serde_json::to_string_pretty(&batteries.batteries) {
+            Ok(out) => println!("{}", out),
+            Err(e) => eprintln!("Failed to format batteries as json: {}", e),
+        }
+        return;
+    }
+
+    for battery in batteries.iter() {
+        if raw {
+            for (key, value, _) in battery.attributes() {
+                println!("{} {} {}", battery.name, key, value);
+            }
+        } else {
+            println!("{}:", battery.name);
+            for (key, value, unit) in battery.attributes() {
+                let mut label = key.replace('_', " ");
+                label[..1].make_ascii_uppercase();
+                println!("  {}: {}{}", label, value, unit);
+            }
+        }
+    }
+}
+
+pub fn print_battery_history(history: &BatteryHistory, raw: bool, json: bool) {
+    if json {
+        match serde_json::to_string_pretty(&history.entries) {
+            Ok(out) => println!("{}", out),
+            Err(e) => eprintln!("Failed to format battery history as json: {}", e),
+        }
+        return;
+    }
+
+    if raw {
+        for entry in &history.entries {
+            println!(
+                "{} {} {} {:.2} {:.2} {}",
+                entry.battery,
+                entry.timestamp,
+                entry.cycle_count,
+                entry.full_wh,
+                entry.design_wh,
+                entry.condition
+            );
+        }
+        return;
+    }
+
+    if history.entries.is_empty() {
+        println!("No battery history yet, it is recorded daily while 'acs run' is running");
+        return;
+    }
+
+    for name in history.battery_names() {
+        let entries = history.for_battery(&name);
+        println!("{}:", name);
+        println!("Date		Cycles	Full	Design	Condition");
+        for entry in &entries {
+            let date: DateTime<Local> =
+                DateTime::<Local>::from(UNIX_EPOCH + Duration::from_secs(entry.timestamp));
+            println!(
+                "{}	{}	{:.1}Wh	{:.1}Wh	{}%",
+                date.format("%Y-%m-%d"),
+                entry.cycle_count,
+                entry.full_wh,
+                entry.design_wh,
+                entry.condition
+            );
+        }
+
+        match wear_rate_per_month(&entries) {
+            Some(rate) => println!("Wear rate: {:.2}% per month\n", rate),
+            None => println!("Wear rate: not enough history yet\n"),
+        }
+    }
+}
+
+pub fn print_platform_profile(profile: String, choices: Vec<String>, raw: bool) {
+    if raw {
+        println!("{}", profile);
+    } else {
+        println!(
+            "Platform profile is {} (can be {})",
+            profile,
+            choices.join(", ")
+        );
+    }
+}
+
 pub fn print_turbo(t: bool, raw: bool) {
     if raw {
         println!("{}", t);
@@ -89,18 +271,64 @@ pub fn print_turbo(t: bool, raw: bool) {
 pub fn print_battery_status(battery: &Battery) -> String {
     if has_battery() {
         format!(
-            "Battery: {}",
+            "Battery: {} ({}){}",
             if battery.status == BatteryStatus::Charging {
                 color!(Color::GREEN, format!("{}%", battery.capacity).as_str())
             } else {
                 color!(Color::RED, format!("{}%", battery.capacity).as_str())
             },
+            battery.status,
+            format_power_draw(
+                battery.power_draw_smoothed,
+                battery.time_to_empty(),
+                battery.time_to_full()
+            ),
         )
     } else {
         format!("Battery: {}", "N/A")
     }
 }
 
+/// Render the combined battery percent followed by each battery when there is more than one
+pub fn print_batteries_status(batteries: &Batteries) -> String {
+    if batteries.is_empty() {
+        return format!("Battery: {}", "N/A");
+    }
+
+    let mut status = format!(
+        "Battery: {} ({}){}",
+        if batteries.status == BatteryStatus::Charging {
+            color!(Color::GREEN, format!("{}%", batteries.capacity).as_str())
+        } else {
+            color!(Color::RED, format!("{}%", batteries.capacity).as_str())
+        },
+        batteries.status,
+        format_power_draw(
+            batteries.power_draw_smoothed(),
+            batteries.time_to_empty(),
+            batteries.time_to_full()
+        ),
+    );
+
+    if batteries.batteries.len() > 1 {
+        for battery in batteries.iter() {
+            status.push_str(&format!(
+                "\n  {}: {}% ({}){}",
+                battery.name,
+                battery.capacity,
+                battery.status,
+                format_power_draw(
+                    battery.power_draw_smoothed,
+                    battery.time_to_empty(),
+                    battery.time_to_full()
+                )
+            ));
+        }
+    }
+
+    status
+}
+
 pub fn print_turbo_animation(cpu: usize, y_pos: usize, delay: u64) {
     let frames = ['◷', '◶', '◵', '◴'];
     let y_pos = cpu + y_pos;
@@ -188,6 +416,24 @@ pub fn print_cpu_governors(cpu_governors: Vec<String>, raw: bool) {
 mod tests {
     use super::*;
 
+    #[test]
+    fn format_power_draw_unit_test() {
+        assert_eq!(
+            format_duration(Duration::from_secs(2 * 3600 + 5 * 60)),
+            "2h 05m"
+        );
+
+        assert_eq!(
+            format_power_draw(8.5, Some(Duration::from_secs(3600)), None),
+            " Draw: 8.50W, 1h 00m until empty"
+        );
+        assert_eq!(
+            format_power_draw(30.0, None, Some(Duration::from_secs(1800))),
+            " Draw: 30.00W, 0h 30m until full"
+        );
+        assert_eq!(format_power_draw(0.0, None, None), "");
+    }
+
     #[test]
     fn render_cpu_unit_test() {
         let new = CPU {
@@ -0,0 +1,164 @@
+#![forbid(unsafe_code)]
+use std::fs::read_dir;
+use std::path::Path;
+
+use crate::sysfs;
+
+const SYSFS_DRM_PATH: &str = "/sys/class/drm/";
+const SYSFS_THUNDERBOLT_PATH: &str = "/sys/bus/thunderbolt/devices/";
+const SYSFS_USB_PATH: &str = "/sys/bus/usb/devices/";
+
+/// Connectors of the built in panel, these are connected even without a dock
+const INTERNAL_CONNECTORS: [&str; 3] = ["eDP", "LVDS", "DSI"];
+
+/// External displays and docks connected to the system
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Dock {
+    /// Connected external display connectors, e.g. card1-HDMI-A-1
+    pub displays: Vec<String>,
+    /// Thunderbolt or USB docks, by their name
+    pub devices: Vec<String>,
+}
+
+impl Dock {
+    pub fn detect() -> Dock {
+        Dock::detect_in(
+            Path::new(SYSFS_DRM_PATH),
+            Path::new(SYSFS_THUNDERBOLT_PATH),
+            Path::new(SYSFS_USB_PATH),
+        )
+    }
+
+    pub fn detect_in(drm: &Path, thunderbolt: &Path, usb: &Path) -> Dock {
+        let mut devices = list_thunderbolt_devices(thunderbolt);
+        devices.append(&mut list_usb_docks(usb));
+
+        Dock {
+            displays: list_external_displays(drm),
+            devices,
+        }
+    }
+
+    /// Docked means an external display or a dock is connected
+    pub fn is_docked(&self) -> bool {
+        !self.displays.is_empty() || !self.devices.is_empty()
+    }
+}
+
+/// Connectors such as card0-HDMI-A-1 whose status is connected, leaving out the built in panel
+fn list_external_displays(drm: &Path) -> Vec<String> {
+    let mut displays = Vec::<String>::new();
+
+    for entry in read_dir(drm).into_iter().flatten().flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        // Connectors are named card<n>-<connector>, the cards themselves are skipped
+        let connector = match name.split_once('-') {
+            Some((card, connector)) if card.starts_with("card") => connector,
+            _ => continue,
+        };
+        if INTERNAL_CONNECTORS.iter().any(|c| connector.starts_with(c)) {
+            continue;
+        }
+
+        let mut status = String::new();
+        if sysfs::read(&mut status, &entry.path().join("status")).is_ok() && status == "connected" {
+            displays.push(name);
+        }
+    }
+
+    displays.sort();
+    displays
+}
+
+/// Thunderbolt devices other than the host controller, which is always route 0
+fn list_thunderbolt_devices(thunderbolt: &Path) -> Vec<String> {
+    let mut devices = Vec::<String>::new();
+
+    for entry in read_dir(thunderbolt).into_iter().flatten().flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        // Devices are named <domain>-<route>, e.g. 0-1, anything else is a domain or port
+        match name.split_once('-') {
+            Some((_, route)) if route != "0" && route.chars().all(|c| c.is_ascii_digit()) => {}
+            _ => continue,
+        }
+
+        let mut device_name = String::new();
+        if sysfs::read(&mut device_name, &entry.path().join("device_name")).is_err() {
+            device_name = name;
+        }
+        devices.push(device_name);
+    }
+
+    devices.sort();
+    devices
+}
+
+/// USB devices that call themselves a dock
+fn list_usb_docks(usb: &Path) -> Vec<String> {
+    let mut docks = Vec::<String>::new();
+
+    for entry in read_dir(usb).into_iter().flatten().flatten() {
+        let mut product = String::new();
+        if sysfs::read(&mut product, &entry.path().join("product")).is_err() {
+            continue;
+        }
+
+        if product.to_lowercase().contains("dock") && !docks.contains(&product) {
+            docks.push(product);
+        }
+    }
+
+    docks.sort();
+    docks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn make_file(parent: &Path, dir: &str, file: &str, content: &str) {
+        fs::create_dir_all(parent.join(dir)).unwrap();
+        fs::write(parent.join(dir).join(file), format!("{}\n", content)).unwrap();
+    }
+
+    #[test]
+    fn detect_dock_unit_test() {
+        let parent = std::env::temp_dir().join("acs_detect_dock_unit_test");
+        fs::remove_dir_all(&parent).ok();
+        let drm = parent.join("drm");
+        let thunderbolt = parent.join("thunderbolt");
+        let usb = parent.join("usb");
+
+        // Only the laptop panel and the host controller
+        make_file(&drm, "card0", "dev", "226:0");
+        make_file(&drm, "card0-eDP-1", "status", "connected");
+        make_file(&drm, "card0-HDMI-A-1", "status", "disconnected");
+        make_file(&thunderbolt, "0-0", "device_name", "Host");
+        make_file(&thunderbolt, "domain0", "security", "user");
+        make_file(&usb, "1-2", "product", "USB Receiver");
+
+        let dock = Dock::detect_in(&drm, &thunderbolt, &usb);
+        assert!(!dock.is_docked());
+
+        make_file(&drm, "card0-HDMI-A-1", "status", "connected");
+        let dock = Dock::detect_in(&drm, &thunderbolt, &usb);
+        assert_eq!(dock.displays, vec!["card0-HDMI-A-1".to_string()]);
+        assert!(dock.is_docked());
+
+        make_file(&drm, "card0-HDMI-A-1", "status", "disconnected");
+        make_file(&thunderbolt, "0-1", "device_name", "Thunderbolt Dock");
+        make_file(&usb, "3-1", "product", "USB-C Dock");
+        let dock = Dock::detect_in(&drm, &thunderbolt, &usb);
+        assert!(dock.displays.is_empty());
+        assert_eq!(
+            dock.devices,
+            vec!["Thunderbolt Dock".to_string(), "USB-C Dock".to_string()]
+        );
+        assert!(dock.is_docked());
+
+        fs::remove_dir_all(&parent).unwrap();
+    }
+}
@@ -8,6 +8,7 @@ pub enum Error {
     IO(std::io::Error),
     TimeError(std::time::SystemTimeError),
     Parse,
+    InvalidValue(String),
     HdwNotFound,
     Unknown,
     DivisionByZero,
@@ -44,6 +45,7 @@ impl fmt::Debug for Error {
                 Error::Unknown => "Unknown error occured".to_string(),
                 Error::DivisionByZero => "Division by zero occured".to_string(),
                 Error::Parse => "Failed to parse data".to_string(),
+                Error::InvalidValue(e) => format!("Invalid value: {}", e),
             }
         )
     }
@@ -1,5 +1,8 @@
 #![forbid(unsafe_code)]
 use std::fmt;
+use std::str::FromStr;
+
+use crate::error::Error;
 
 /// Governor
 ///
@@ -21,3 +24,19 @@ impl fmt::Display for Gov {
         write!(f, "{}", name)
     }
 }
+
+impl FromStr for Gov {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Gov, Error> {
+        match name {
+            "powersave" => Ok(Gov::Powersave),
+            "performance" => Ok(Gov::Performance),
+            "schedutil" => Ok(Gov::Schedutil),
+            _ => Err(Error::InvalidValue(format!(
+                "acs can only set the powersave, performance and schedutil governors, not {}",
+                name
+            ))),
+        }
+    }
+}
@@ -52,21 +52,18 @@ impl Grapher for Graph {
     }
 }
 
-#[derive(PartialEq, Eq, Clone, Debug)]
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
 pub enum GraphType {
+    #[default]
     Hidden,
     Frequency,
     Usage,
     Temperature,
+    /// Power going in or out of the battery in watts
+    BatteryDraw,
     Unknown,
 }
 
-impl Default for GraphType {
-    fn default() -> GraphType {
-        GraphType::Hidden
-    }
-}
-
 impl fmt::Display for GraphType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -74,6 +71,7 @@ impl fmt::Display for GraphType {
             GraphType::Frequency => write!(f, "frequency"),
             GraphType::Usage => write!(f, "usage"),
             GraphType::Temperature => write!(f, "temperature"),
+            GraphType::BatteryDraw => write!(f, "battery draw"),
             GraphType::Unknown => write!(f, "unknown"),
         }
     }
@@ -86,6 +84,7 @@ pub fn get_graph_type(graph_type: &str) -> GraphType {
         "freq" => GraphType::Frequency,
         "usage" => GraphType::Usage,
         "temp" => GraphType::Temperature,
+        "draw" => GraphType::BatteryDraw,
         _ => GraphType::Unknown,
     }
 }
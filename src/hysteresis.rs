@@ -0,0 +1,95 @@
+#![forbid(unsafe_code)]
+//! Hysteresis and debouncing for the built in rules
+//!
+//! Without it a temperature hovering around overheat_threshold would change the governor on every
+//! update. A rule can leave at a different threshold than it enters at, and a change only counts
+//! once it has lasted for long enough.
+
+use serde::{Deserialize, Serialize};
+use std::time::SystemTime;
+
+/// How a rule enters and leaves, configured per rule in the hysteresis table
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Hysteresis {
+    /// The threshold the rule stops applying at, the rule's own threshold is used to enter it
+    pub exit_threshold: Option<i8>,
+    /// Seconds the rule must hold before it applies
+    pub enter_secs: Option<u64>,
+    /// Seconds the rule must stop holding before it stops applying
+    pub exit_secs: Option<u64>,
+}
+
+/// Turns a condition that is checked every update into whether a rule applies
+#[derive(Clone, Debug, Default)]
+pub struct Debounce {
+    applying: bool,
+    /// When the condition started to disagree with applying
+    changing_since: Option<SystemTime>,
+}
+
+impl Debounce {
+    pub fn is_applying(&self) -> bool {
+        self.applying
+    }
+
+    /// Check the condition again, returns if the rule applies
+    pub fn update(
+        &mut self,
+        holds: bool,
+        enter_secs: u64,
+        exit_secs: u64,
+        now: SystemTime,
+    ) -> bool {
+        if holds == self.applying {
+            self.changing_since = None;
+            return self.applying;
+        }
+
+        let since = *self.changing_since.get_or_insert(now);
+        let needed = if holds { enter_secs } else { exit_secs };
+        if now.duration_since(since).unwrap_or_default().as_secs() >= needed {
+            self.applying = holds;
+            self.changing_since = None;
+        }
+
+        self.applying
+    }
+
+    /// Seconds until the rule starts or stops applying, None when nothing is changing
+    pub fn remaining_secs(&self, enter_secs: u64, exit_secs: u64, now: SystemTime) -> Option<u64> {
+        let since = self.changing_since?;
+        let needed = if self.applying { exit_secs } else { enter_secs };
+        Some(needed.saturating_sub(now.duration_since(since).unwrap_or_default().as_secs()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn debounce_unit_test() {
+        let start = SystemTime::UNIX_EPOCH;
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut debounce = Debounce::default();
+
+        // Nothing to wait for
+        assert!(debounce.update(true, 0, 0, at(0)));
+        assert!(!debounce.update(false, 0, 0, at(1)));
+
+        // Entering takes 10 seconds
+        assert!(!debounce.update(true, 10, 5, at(10)));
+        assert!(!debounce.update(true, 10, 5, at(15)));
+        assert!(debounce.update(true, 10, 5, at(20)));
+
+        // A short dip does not leave, and restarts the time needed to leave
+        assert!(debounce.update(false, 10, 5, at(21)));
+        assert!(debounce.update(true, 10, 5, at(22)));
+        assert!(debounce.update(false, 10, 5, at(23)));
+        assert!(debounce.update(false, 10, 5, at(27)));
+        assert_eq!(debounce.remaining_secs(10, 5, at(27)), Some(1));
+        assert!(!debounce.update(false, 10, 5, at(28)));
+        assert_eq!(debounce.remaining_secs(10, 5, at(28)), None);
+    }
+}
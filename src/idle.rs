@@ -0,0 +1,277 @@
+#![forbid(unsafe_code)]
+//! Detects when nobody is using the keyboard or mouse, for the idle rule
+//!
+//! Each keyboard and mouse in /dev/input is read on its own thread, which only records when the
+//! last event came in. Devices are looked for again every few seconds, so that a keyboard plugged
+//! in later or a dock that reconnects is read too. Reading /dev/input needs root, so without it
+//! the interrupts of the built in keyboard and touchpad in /proc/interrupts are counted instead.
+
+use std::collections::BTreeSet;
+use std::fs::{read_dir, read_to_string, File};
+use std::io::Read;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::error::Error;
+
+const DEV_INPUT_PATH: &str = "/dev/input/";
+const SYSFS_INPUT_PATH: &str = "/sys/class/input/";
+const PROC_INTERRUPTS_PATH: &str = "/proc/interrupts";
+
+/// How often /sys/class/input is checked for keyboards and mice that were plugged in
+const RESCAN_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Interrupts of the built in keyboard and touchpad, USB input shares its interrupt with every
+/// other USB device so it can only be seen through /dev/input
+const INPUT_IRQS: [&str; 3] = ["i8042", "i2c_hid", "i2c-hid"];
+
+/// Event types from linux/input-event-codes.h, keys and buttons or relative movement
+const EV_KEY: u64 = 0x01;
+const EV_REL: u64 = 0x02;
+
+/// Where activity is read from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum IdleSource {
+    /// The devices in /dev/input
+    Input,
+    /// The last total of the input interrupts
+    Interrupts(u64),
+}
+
+/// Keeps track of when the keyboard or mouse was last used
+#[derive(Debug)]
+pub struct IdleMonitor {
+    source: Option<IdleSource>,
+    last_activity: Arc<Mutex<SystemTime>>,
+    /// The devices being read, each reader removes its device when the device is gone
+    devices: Arc<Mutex<BTreeSet<String>>>,
+    /// When /sys/class/input was last checked, None until the monitor is started
+    last_scan: Option<SystemTime>,
+}
+
+impl Default for IdleMonitor {
+    fn default() -> IdleMonitor {
+        IdleMonitor {
+            source: None,
+            last_activity: Arc::new(Mutex::new(SystemTime::now())),
+            devices: Arc::new(Mutex::new(BTreeSet::new())),
+            last_scan: None,
+        }
+    }
+}
+
+impl IdleMonitor {
+    /// Start watching for input, /dev/input is preferred over /proc/interrupts
+    pub fn start(&mut self) -> Result<(), Error> {
+        self.last_scan = Some(SystemTime::now());
+        if self.scan_input_devices() > 0 {
+            self.source = Some(IdleSource::Input);
+            return Ok(());
+        }
+
+        let interrupts = read_to_string(PROC_INTERRUPTS_PATH)?;
+        let count = count_input_interrupts(&interrupts).ok_or(Error::HdwNotFound)?;
+        self.source = Some(IdleSource::Interrupts(count));
+        Ok(())
+    }
+
+    /// Start reading the keyboards and mice that are not being read yet, returns how many
+    fn scan_input_devices(&mut self) -> usize {
+        let mut started = 0;
+        for device in list_input_devices() {
+            if self.devices.lock().unwrap().contains(&device) {
+                continue;
+            }
+
+            if let Ok(file) = File::open(Path::new(DEV_INPUT_PATH).join(&device)) {
+                self.read_device(device, file);
+                started += 1;
+            }
+        }
+        started
+    }
+
+    /// Record every event from a device on its own thread
+    fn read_device(&self, device: String, mut file: File) {
+        self.devices.lock().unwrap().insert(device.clone());
+        let devices = Arc::clone(&self.devices);
+        let last_activity = Arc::clone(&self.last_activity);
+        thread::spawn(move || {
+            let mut buffer = [0u8; 256];
+            // The read blocks until there are events, it ends when the device is removed
+            while let Ok(read) = file.read(&mut buffer) {
+                if read == 0 {
+                    break;
+                }
+                *last_activity.lock().unwrap() = SystemTime::now();
+            }
+            // So that the device is read again if it comes back
+            devices.lock().unwrap().remove(&device);
+        });
+    }
+
+    /// A description of where input is read from, e.g. 3 input devices
+    pub fn source(&self) -> Option<String> {
+        self.source.map(|source| match source {
+            IdleSource::Input => format!("{} input device(s)", self.devices.lock().unwrap().len()),
+            IdleSource::Interrupts(_) => String::from("input interrupts"),
+        })
+    }
+
+    /// Look for new keyboards and mice and check the interrupts again, the threads keep
+    /// themselves up to date
+    pub fn update(&mut self, now: SystemTime) {
+        if let Some(last_scan) = self.last_scan {
+            if now.duration_since(last_scan).unwrap_or_default() >= RESCAN_INTERVAL {
+                self.last_scan = Some(now);
+                if self.scan_input_devices() > 0 {
+                    // Plugging in a keyboard or mouse means somebody is there
+                    *self.last_activity.lock().unwrap() = now;
+                    self.source = Some(IdleSource::Input);
+                }
+            }
+        }
+
+        if let Some(IdleSource::Interrupts(last)) = self.source {
+            let count = read_to_string(PROC_INTERRUPTS_PATH)
+                .ok()
+                .and_then(|interrupts| count_input_interrupts(&interrupts));
+            if let Some(count) = count {
+                if count != last {
+                    *self.last_activity.lock().unwrap() = now;
+                    self.source = Some(IdleSource::Interrupts(count));
+                }
+            }
+        }
+    }
+
+    /// Seconds since the last input, None when input can not be watched
+    pub fn idle_secs(&self, now: SystemTime) -> Option<u64> {
+        self.source?;
+        let last_activity = *self.last_activity.lock().unwrap();
+        Some(
+            now.duration_since(last_activity)
+                .unwrap_or_default()
+                .as_secs(),
+        )
+    }
+}
+
+/// Event devices such as event3 that have keys, buttons or relative movement, which leaves out
+/// switches and sensors like accelerometers that send events without anybody using them
+fn list_input_devices() -> Vec<String> {
+    let mut devices = Vec::<String>::new();
+
+    for entry in read_dir(SYSFS_INPUT_PATH).into_iter().flatten().flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !name.starts_with("event") {
+            continue;
+        }
+
+        let capabilities =
+            read_to_string(entry.path().join("device/capabilities/ev")).unwrap_or_default();
+        if is_user_input(&capabilities) {
+            devices.push(name);
+        }
+    }
+
+    devices.sort();
+    devices
+}
+
+/// If the event types in capabilities/ev include keys or relative movement
+fn is_user_input(capabilities: &str) -> bool {
+    u64::from_str_radix(capabilities.trim(), 16)
+        .map(|ev| ev & (1 << EV_KEY | 1 << EV_REL) != 0)
+        .unwrap_or(false)
+}
+
+/// The total of the interrupts from input devices on every cpu, None if there are none
+fn count_input_interrupts(interrupts: &str) -> Option<u64> {
+    let mut total = None;
+
+    for line in interrupts.lines() {
+        if !INPUT_IRQS.iter().any(|irq| line.contains(irq)) {
+            continue;
+        }
+
+        // The irq number comes first and is followed by a count for each cpu
+        let count: u64 = line
+            .split_whitespace()
+            .skip(1)
+            .map_while(|column| column.parse::<u64>().ok())
+            .sum();
+        total = Some(total.unwrap_or(0) + count);
+    }
+
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_input_interrupts_unit_test() {
+        let interrupts = "            CPU0       CPU1
+   0:         10          0   IO-APIC    2-edge      timer
+   1:        120         35   IO-APIC    1-edge      i8042
+  12:          4        300   IO-APIC   12-edge      i8042
+  51:          0       1500   IR-IO-APIC   51-fasteoi   i2c_designware.1, idma64.1
+ 127:         80          0   IR-PCI-MSI 327680-edge      xhci_hcd
+ 156:         21          1   amd_gpio   10  i2c_hid
+";
+        assert_eq!(count_input_interrupts(interrupts), Some(481));
+        assert_eq!(count_input_interrupts("   0:  10  IO-APIC  timer"), None);
+    }
+
+    #[test]
+    fn is_user_input_unit_test() {
+        // Keyboard
+        assert!(is_user_input("120013\n"));
+        // Mouse
+        assert!(is_user_input("17"));
+        // Lid switch
+        assert!(!is_user_input("21"));
+        // Accelerometer
+        assert!(!is_user_input("9"));
+        assert!(!is_user_input(""));
+    }
+
+    #[test]
+    fn idle_secs_unit_test() {
+        let now = SystemTime::now();
+        let mut idle = IdleMonitor::default();
+        assert_eq!(idle.idle_secs(now), None);
+
+        idle.source = Some(IdleSource::Input);
+        *idle.last_activity.lock().unwrap() = now;
+        assert_eq!(idle.idle_secs(now + Duration::from_secs(90)), Some(90));
+    }
+
+    #[test]
+    fn read_device_unit_test() -> Result<(), Error> {
+        let path = std::env::temp_dir().join("acs_read_device_unit_test");
+        std::fs::write(&path, [0u8; 24])?;
+        let start = SystemTime::now() - Duration::from_secs(60);
+
+        let idle = IdleMonitor::default();
+        *idle.last_activity.lock().unwrap() = start;
+        idle.read_device("event7".to_string(), File::open(&path)?);
+
+        // The file ends like a device that was unplugged, which frees it to be read again
+        for _ in 0..100 {
+            if idle.devices.lock().unwrap().is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(idle.devices.lock().unwrap().is_empty());
+        assert!(*idle.last_activity.lock().unwrap() > start);
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
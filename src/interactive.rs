@@ -1,6 +1,9 @@
 #![forbid(unsafe_code)]
 use super::config::{get_config, Config};
-use super::interface::{CoreInterface, DaemonControl, DaemonController, Get, Getter, Set, Setter};
+use super::interface::{
+    CoreInterface, DaemonControl, DaemonController, Get, Getter, ProfileControl, ProfileController,
+    Set, Setter,
+};
 use super::settings::Settings;
 use efcl::{bold, color, Color};
 use std::io::{stdin, stdout, Write};
@@ -9,6 +12,8 @@ pub fn help() {
     const HELP_TEXT: &str = "\
 - exit
 
+- explain
+
 - get
   - freq
   - cpus
@@ -20,9 +25,20 @@ pub fn help() {
   - speeds
   - available_governors
   - battery_condition
+  - battery
+  - battery history
+  - platform-profile
 
 - set
   - gov
+  - platform-profile <profile>
+  - charge-limit <start> <end>
+
+- profile
+  - list
+  - get
+  - set <profile>
+  - reset
 
 - daemon
   - disable
@@ -41,6 +57,7 @@ pub fn interactive() {
         set: Set {},
         get: Get {},
         dec: DaemonControl {},
+        pro: ProfileControl {},
     };
 
     let mut input;
@@ -72,6 +89,9 @@ pub fn interactive() {
                     "get temp" => int.get.temp(false),
                     "get govs" => int.get.govs(false),
                     "get battery_condition" => int.get.bat_cond(false),
+                    "get battery" => int.get.battery(false, false, false),
+                    "get battery history" => int.get.battery(false, false, true),
+                    "get platform-profile" => int.get.platform_profile(false),
 
                     "set gov performance" => {
                         let config: Config = get_config();
@@ -86,6 +106,31 @@ pub fn interactive() {
                         int.set
                             .gov("powersave".to_string(), config, set_settings.clone());
                     }
+                    _ if new.starts_with("set platform-profile ") => int
+                        .set
+                        .platform_profile(new["set platform-profile ".len()..].trim().to_string()),
+                    _ if new.starts_with("set charge-limit ") => {
+                        let limits: Vec<Result<u8, _>> = new["set charge-limit ".len()..]
+                            .split_whitespace()
+                            .map(|x| x.parse::<u8>())
+                            .collect();
+
+                        match limits.as_slice() {
+                            [Ok(start), Ok(end)] => int.set.charge_limit(*start, *end),
+                            _ => println!(
+                                "{}",
+                                color!(Color::RED, "Usage: set charge-limit <start> <end>")
+                            ),
+                        }
+                    }
+                    "explain" => int.dec.explain(false),
+                    "profile list" => int.pro.list(&get_config()),
+                    "profile get" => int.pro.get(&get_config()),
+                    _ if new.starts_with("profile set ") => int.pro.set(
+                        new["profile set ".len()..].trim().to_string(),
+                        &get_config(),
+                    ),
+                    "profile reset" => int.pro.reset(),
                     "daemon disable" => int.dec.disable(),
                     "daemon enable" => int.dec.enable(),
                     "daemon status" => int.dec.status(),
@@ -9,18 +9,24 @@
 use super::config::Config;
 use super::daemon::{daemon_init, Checker};
 use super::display::{
-    print_available_governors, print_bat_cond, print_cpu_governors, print_cpu_speeds,
-    print_cpu_temp, print_cpus, print_freq, print_power, print_turbo,
+    print_available_governors, print_bat_cond, print_batteries, print_battery_history,
+    print_cpu_governors, print_cpu_speeds, print_cpu_temp, print_cpus, print_freq,
+    print_platform_profile, print_power, print_turbo,
 };
-use super::power::battery::Battery;
+use super::platform_profile::PlatformProfile;
+use super::power::battery::{Batteries, Battery};
+use super::power::charge_limit::ChargeThresholds;
+use super::power::history::BatteryHistory;
 use super::power::lid::{Lid, LidRetriever};
 use super::power::{Power, PowerRetriever};
+use super::profiles::{load_active_profile, save_active_profile};
 use super::settings::Settings;
 use super::system::{
     check_available_governors, check_cpu_freq, check_cpu_name, check_turbo_enabled,
     get_cpu_percent, list_cpu_governors, list_cpu_speeds, list_cpu_temp, list_cpus,
 };
 use super::thermal::read_thermal_zones;
+use crate::error::Error;
 use crate::network::send::query_one;
 
 pub struct DaemonControl {}
@@ -30,9 +36,34 @@ pub trait DaemonController {
     fn enable(&self);
     fn status(&self);
     fn toggle(&self);
+    fn explain(&self, json: bool);
 }
 
 impl DaemonController for DaemonControl {
+    fn explain(&self, json: bool) {
+        match query_one("/tmp/acs.sock", crate::network::Packet::ExplainRequest()) {
+            Ok(packet) => match packet {
+                crate::network::Packet::ExplainResponse(Some(decision)) => {
+                    if json {
+                        match serde_json::to_string_pretty(&decision) {
+                            Ok(json) => println!("{}", json),
+                            Err(e) => eprintln!("Could not serialize the decision: {:?}", e),
+                        }
+                    } else {
+                        print!("{}", decision)
+                    }
+                }
+                crate::network::Packet::ExplainResponse(None) => {
+                    println!("The running daemon has not made a decision yet")
+                }
+                _ => println!("Failed: Unexpected response packet"),
+            },
+            Err(e) => {
+                println!("{:?}", e)
+            }
+        }
+    }
+
     fn disable(&self) {
         match query_one(
             "/tmp/acs.sock",
@@ -138,6 +169,8 @@ pub trait Getter {
     fn temp(&self, raw: bool);
     fn govs(&self, raw: bool);
     fn bat_cond(&self, raw: bool);
+    fn battery(&self, raw: bool, json: bool, history: bool);
+    fn platform_profile(&self, raw: bool);
 }
 
 impl Getter for Get {
@@ -147,20 +180,17 @@ impl Getter for Get {
     }
 
     fn power(&self, raw: bool) {
-        let mut battery = match Battery::new() {
-            Ok(plugged) => plugged,
+        let mut batteries = match Batteries::new() {
+            Ok(batteries) => batteries,
             Err(e) => {
                 eprintln!("Failed to get battery, an error occured: {:?}", e);
                 return;
             }
         };
-        match battery.update() {
-            Ok(plugged) => plugged,
-            Err(e) => {
-                eprintln!("Failed to update battery, an error occured: {:?}", e);
-                return;
-            }
-        };
+        // Show whatever could be read even if part of the battery could not
+        if let Err(e) = batteries.update() {
+            eprintln!("Failed to update battery, an error occured: {:?}", e);
+        }
         let power = Power::new();
 
         let plugged = match power.read_power_source() {
@@ -180,7 +210,7 @@ impl Getter for Get {
             }
         };
 
-        print_power(lid, battery.capacity, plugged, raw);
+        print_power(lid, &batteries, &power.supplies(), plugged, raw);
     }
 
     fn usage(&self, raw: bool, delay: Option<u64>) {
@@ -263,12 +293,50 @@ impl Getter for Get {
         };
         print_bat_cond(battery.condition, raw)
     }
+
+    fn battery(&self, raw: bool, json: bool, history: bool) {
+        if history {
+            match BatteryHistory::load() {
+                Ok(history) => print_battery_history(&history, raw, json),
+                Err(e) => eprintln!("Failed to read battery history, an error occured: {:?}", e),
+            }
+            return;
+        }
+
+        let mut batteries = match Batteries::new() {
+            Ok(batteries) => batteries,
+            Err(e) => {
+                eprintln!("Failed to get battery, an error occured: {:?}", e);
+                return;
+            }
+        };
+        if let Err(e) = batteries.update() {
+            eprintln!("Failed to update battery, an error occured: {:?}", e);
+            return;
+        }
+        print_batteries(&batteries, raw, json);
+    }
+
+    fn platform_profile(&self, raw: bool) {
+        let platform_profile = PlatformProfile::new();
+        match (platform_profile.read(), platform_profile.choices()) {
+            (Ok(profile), Ok(choices)) => print_platform_profile(profile, choices, raw),
+            (Err(Error::HdwNotFound), _) | (_, Err(Error::HdwNotFound)) => {
+                eprintln!("This system does not support platform profiles")
+            }
+            (Err(e), _) | (_, Err(e)) => {
+                eprintln!("Failed to read platform profile, an error occured: {:?}", e)
+            }
+        }
+    }
 }
 
 pub struct Set {}
 
 pub trait Setter {
     fn gov(&self, value: String, config: Config, settings: Settings);
+    fn charge_limit(&self, start: u8, end: u8);
+    fn platform_profile(&self, value: String);
 }
 
 impl Setter for Set {
@@ -280,10 +348,152 @@ impl Setter for Set {
             Err(e) => eprint!("Could not set gov, {:?}", e),
         };
     }
+
+    fn charge_limit(&self, start: u8, end: u8) {
+        let thresholds = match ChargeThresholds::new(start, end) {
+            Ok(thresholds) => thresholds,
+            Err(e) => {
+                eprint!("{:?}", e);
+                return;
+            }
+        };
+
+        let batteries = match Batteries::new() {
+            Ok(batteries) => batteries,
+            Err(e) => {
+                eprintln!("Failed to get battery, an error occured: {:?}", e);
+                return;
+            }
+        };
+
+        match batteries.set_charge_thresholds(&thresholds) {
+            Ok(controls) => {
+                for control in controls {
+                    println!("Set charge limit to {} using {}", thresholds, control);
+                }
+            }
+            Err(Error::HdwNotFound) => {
+                eprintln!("This laptop does not support charge thresholds")
+            }
+            Err(e) => eprint!("Could not set charge limit, {:?}", e),
+        }
+    }
+
+    fn platform_profile(&self, value: String) {
+        match PlatformProfile::new().set(&value) {
+            Ok(_) => println!("Set platform profile to {}", value),
+            Err(Error::HdwNotFound) => {
+                eprintln!("This system does not support platform profiles")
+            }
+            Err(e) => eprint!("Could not set platform profile, {:?}", e),
+        }
+    }
+}
+
+pub struct ProfileControl {}
+
+pub trait ProfileController {
+    fn list(&self, config: &Config);
+    fn get(&self, config: &Config);
+    fn set(&self, value: String, config: &Config);
+    fn reset(&self);
+}
+
+/// The profile of the running daemon, or the one it will start with when it is not running
+fn active_profile() -> Option<String> {
+    match query_one("/tmp/acs.sock", crate::network::Packet::ProfileGetRequest()) {
+        Ok(crate::network::Packet::ProfileGetResponse(profile)) => profile,
+        _ => load_active_profile().ok().flatten(),
+    }
+}
+
+fn query_set_profile(profile: Option<String>) {
+    match query_one(
+        "/tmp/acs.sock",
+        crate::network::Packet::ProfileSetRequest(profile.clone()),
+    ) {
+        Ok(packet) => match packet {
+            crate::network::Packet::ProfileSetResponse(success) => match (success, profile) {
+                (true, Some(profile)) => {
+                    println!("The running daemon is using profile {}", profile)
+                }
+                (true, None) => println!("The running daemon is no longer using a profile"),
+                (false, _) => println!("The running daemon could not switch profile, see its log"),
+            },
+            _ => println!("Failed: Unexpected response packet"),
+        },
+        // The daemon is not running, so keep the profile for when it starts
+        Err(e) => match (save_active_profile(profile.as_deref()), profile) {
+            (Ok(_), Some(profile)) => println!(
+                "The daemon is not running, profile {} will be used when it next starts",
+                profile
+            ),
+            (Ok(_), None) => {
+                println!("The daemon is not running, no profile will be used when it next starts")
+            }
+            (Err(save_error), _) => eprintln!(
+                "Could not reach the daemon ({:?}) or save the profile for its next start ({:?})",
+                e, save_error
+            ),
+        },
+    }
+}
+
+impl ProfileController for ProfileControl {
+    fn list(&self, config: &Config) {
+        if config.profiles.is_empty() {
+            println!("There are no profiles in the config");
+            return;
+        }
+
+        let active = active_profile();
+        for name in config.profiles.keys() {
+            let marker = if active.as_ref() == Some(name) {
+                "*"
+            } else {
+                " "
+            };
+            println!("{} {}", marker, name);
+        }
+    }
+
+    fn get(&self, config: &Config) {
+        match active_profile() {
+            Some(name) => {
+                println!("Profile: {}", name);
+                if let Some(profile) = config.profiles.get(&name) {
+                    match toml::to_string(profile) {
+                        Ok(settings) => print!("{}", settings),
+                        Err(e) => eprintln!("Could not show profile {}: {:?}", name, e),
+                    }
+                }
+            }
+            None => println!("No profile is active"),
+        }
+    }
+
+    fn set(&self, value: String, config: &Config) {
+        // Checking here gives a better message than the daemon can send back
+        if !config.profiles.contains_key(&value) {
+            let names: Vec<&str> = config.profiles.keys().map(|n| n.as_str()).collect();
+            eprintln!(
+                "{} is not a profile, the profiles are: {}",
+                value,
+                names.join(", ")
+            );
+            return;
+        }
+        query_set_profile(Some(value));
+    }
+
+    fn reset(&self) {
+        query_set_profile(None);
+    }
 }
 
 pub struct CoreInterface {
     pub get: Get,
     pub set: Set,
     pub dec: DaemonControl,
+    pub pro: ProfileControl,
 }
@@ -3,16 +3,24 @@ pub mod config;
 pub mod cpu;
 pub mod csv;
 pub mod daemon;
+pub mod decision;
 pub mod display;
+pub mod dock;
 pub mod error;
 pub mod gov;
 pub mod graph;
+pub mod hysteresis;
+pub mod idle;
 pub mod interactive;
 pub mod interface;
 pub mod logger;
 pub mod network;
+pub mod platform_profile;
 pub mod power;
+pub mod predictor;
 pub mod proc;
+pub mod profiles;
+pub mod rules;
 pub mod settings;
 pub mod setup;
 pub mod sysfs;
@@ -1,6 +1,7 @@
 #![forbid(unsafe_code)]
 use serde::{Deserialize, Serialize};
 
+use crate::decision::Decision;
 use crate::logger::Log;
 
 use super::daemon::Daemon;
@@ -19,7 +20,7 @@ pub mod hook;
 pub mod listen;
 pub mod send;
 
-#[derive(PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[derive(PartialEq, Debug, Serialize, Deserialize)]
 pub enum Packet {
     Hello(String),
     HelloResponse(String, u32),
@@ -32,6 +33,14 @@ pub enum Packet {
     DaemonLogRequest(),
     DaemonLogResponse(Vec<Log>),
     DaemonLogEvent(Log),
+    ProfileGetRequest(),
+    ProfileGetResponse(Option<String>),
+    /// None goes back to the config without a profile
+    ProfileSetRequest(Option<String>),
+    ProfileSetResponse(bool),
+    ExplainRequest(),
+    /// None until the daemon has made its first decision
+    ExplainResponse(Option<Box<Decision>>),
     Unknown,
 }
 
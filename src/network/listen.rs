@@ -152,6 +152,43 @@ pub fn handle_stream(stream: UnixStream, c_daemon_mutex: &Arc<Mutex<Daemon>>) {
                 Packet::DaemonLogRequest() => {}
                 Packet::DaemonLogResponse(_) => {}
                 Packet::DaemonLogEvent(_) => {}
+                Packet::ProfileGetRequest() => {
+                    let response = Packet::ProfileGetResponse(
+                        inner_daemon_mutex.lock().unwrap().profile.clone(),
+                    );
+                    let mut writer = BufWriter::new(&stream);
+                    write_packet!(writer, response);
+                }
+                Packet::ProfileGetResponse(_) => {}
+                Packet::ProfileSetRequest(profile) => {
+                    let mut inner_daemon = inner_daemon_mutex.lock().unwrap();
+                    let response = match inner_daemon.set_profile(profile.clone()) {
+                        Ok(_) => Packet::ProfileSetResponse(true),
+                        Err(e) => {
+                            inner_daemon.logger.log(
+                                &format!("Could not set profile {:?}: {:?}", profile, e),
+                                logger::Severity::Warning,
+                            );
+                            Packet::ProfileSetResponse(false)
+                        }
+                    };
+                    let mut writer = BufWriter::new(&stream);
+                    write_packet!(writer, response);
+                }
+                Packet::ProfileSetResponse(_) => {}
+                Packet::ExplainRequest() => {
+                    let response = Packet::ExplainResponse(
+                        inner_daemon_mutex
+                            .lock()
+                            .unwrap()
+                            .decision
+                            .clone()
+                            .map(Box::new),
+                    );
+                    let mut writer = BufWriter::new(&stream);
+                    write_packet!(writer, response);
+                }
+                Packet::ExplainResponse(_) => {}
             };
         }
     });
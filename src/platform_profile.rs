@@ -0,0 +1,118 @@
+#![forbid(unsafe_code)]
+//! ACPI platform profiles
+//!
+//! Many laptops let the firmware choose fan curves and power limits through
+//! /sys/firmware/acpi/platform_profile, e.g. low-power, balanced or performance. The profiles a
+//! laptop supports are listed in platform_profile_choices.
+
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+use crate::sysfs;
+
+const SYSFS_ACPI_PATH: &str = "/sys/firmware/acpi/";
+
+pub struct PlatformProfile {
+    parent_path: PathBuf,
+}
+
+impl PlatformProfile {
+    pub fn new() -> PlatformProfile {
+        PlatformProfile::from_parent_path(Path::new(SYSFS_ACPI_PATH))
+    }
+
+    /// Use a directory other than /sys/firmware/acpi
+    pub fn from_parent_path(parent_path: &Path) -> PlatformProfile {
+        PlatformProfile {
+            parent_path: parent_path.to_path_buf(),
+        }
+    }
+
+    /// If this system supports platform profiles
+    pub fn is_supported(&self) -> bool {
+        self.parent_path.join("platform_profile").exists()
+    }
+
+    /// The current platform profile, e.g. balanced
+    pub fn read(&self) -> Result<String, Error> {
+        if !self.is_supported() {
+            return Err(Error::HdwNotFound);
+        }
+
+        let mut profile = String::new();
+        sysfs::read(&mut profile, &self.parent_path.join("platform_profile"))?;
+        Ok(profile)
+    }
+
+    /// Every platform profile this system supports
+    pub fn choices(&self) -> Result<Vec<String>, Error> {
+        if !self.is_supported() {
+            return Err(Error::HdwNotFound);
+        }
+
+        let mut choices = String::new();
+        sysfs::read(
+            &mut choices,
+            &self.parent_path.join("platform_profile_choices"),
+        )?;
+        Ok(choices.split_whitespace().map(|c| c.to_string()).collect())
+    }
+
+    /// Set the platform profile, it must be one of the choices
+    pub fn set(&self, profile: &str) -> Result<(), Error> {
+        let choices = self.choices()?;
+        if !choices.iter().any(|c| c == profile) {
+            return Err(Error::InvalidValue(format!(
+                "platform profile '{}' is not one of {}",
+                profile,
+                choices.join(", ")
+            )));
+        }
+
+        sysfs::write(&self.parent_path.join("platform_profile"), profile)
+    }
+}
+
+impl Default for PlatformProfile {
+    fn default() -> Self {
+        PlatformProfile::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn platform_profile_unit_test() -> Result<(), Error> {
+        let parent = std::env::temp_dir().join("acs_platform_profile_unit_test");
+        fs::remove_dir_all(&parent).ok();
+        fs::create_dir_all(&parent)?;
+
+        let profile = PlatformProfile::from_parent_path(&parent);
+        assert!(!profile.is_supported());
+        assert!(matches!(profile.read(), Err(Error::HdwNotFound)));
+
+        fs::write(parent.join("platform_profile"), "balanced\n")?;
+        fs::write(
+            parent.join("platform_profile_choices"),
+            "low-power balanced performance\n",
+        )?;
+
+        assert_eq!(profile.read()?, "balanced");
+        assert_eq!(
+            profile.choices()?,
+            vec!["low-power", "balanced", "performance"]
+        );
+
+        profile.set("performance")?;
+        assert_eq!(profile.read()?, "performance");
+
+        assert!(matches!(profile.set("quiet"), Err(Error::InvalidValue(_))));
+        assert_eq!(profile.read()?, "performance");
+
+        fs::remove_dir_all(&parent)?;
+        Ok(())
+    }
+}
@@ -1,72 +1,383 @@
 #![forbid(unsafe_code)]
-use std::fs;
-use std::path::Path;
+use std::fmt;
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
 
 use crate::error::Error;
+use crate::sysfs;
 
 pub mod battery;
+pub mod charge_limit;
+pub mod history;
 pub mod lid;
+pub mod ups;
 
-/// Called once at the start of read_power_source
-/// Discover the path to the AC power_supply
-pub fn set_best_path() -> Option<&'static str> {
-    // Only loaded once
-    static POWER_SOURCE_PATH: [&str; 4] = [
-        "/sys/class/power_supply/AC/online",
-        "/sys/class/power_supply/AC0/online",
-        "/sys/class/power_supply/AC1/online",
-        "/sys/class/power_supply/ACAD/online",
-    ];
-
-    // Find if any AC power path exists
-    for path in POWER_SOURCE_PATH {
-        if Path::new(path).exists() {
-            // Mutate Power struct and leave
-            return Some(path);
+// SYSFS root path
+const SYSFS_POWER_SUPPLY_PATH: &str = "/sys/class/power_supply/";
+const SYSFS_TYPEC_PATH: &str = "/sys/class/typec/";
+
+/// The kind of power_supply as reported by its type file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PowerSupplyType {
+    /// A regular AC adapter, e.g. AC, AC0, ACAD or ADP1
+    Mains,
+    /// USB power, including USB-C adapters e.g. ucsi-source-psy-USBC000:001
+    USB,
+    Battery,
+    UPS,
+    Wireless,
+    Unknown,
+}
+
+impl PowerSupplyType {
+    fn from_type(supply_type: &str) -> PowerSupplyType {
+        match supply_type {
+            "Mains" => PowerSupplyType::Mains,
+            "USB" => PowerSupplyType::USB,
+            "Battery" => PowerSupplyType::Battery,
+            "UPS" => PowerSupplyType::UPS,
+            "Wireless" => PowerSupplyType::Wireless,
+            _ => PowerSupplyType::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for PowerSupplyType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PowerSupplyType::Mains => write!(f, "Mains"),
+            PowerSupplyType::USB => write!(f, "USB"),
+            PowerSupplyType::Battery => write!(f, "Battery"),
+            PowerSupplyType::UPS => write!(f, "UPS"),
+            PowerSupplyType::Wireless => write!(f, "Wireless"),
+            PowerSupplyType::Unknown => write!(f, "Unknown"),
+        }
+    }
+}
+
+/// An entry in /sys/class/power_supply
+#[derive(Clone, Debug)]
+pub struct PowerSupply {
+    pub name: String,
+    pub path: PathBuf,
+    pub supply_type: PowerSupplyType,
+    /// If power is being supplied, always false for batteries
+    pub online: bool,
+    /// "System" for things that power the computer, "Device" for things like a wireless mouse
+    pub scope: String,
+    /// The negotiated power this supply can deliver in watts, None when it cannot be read
+    pub wattage: Option<f32>,
+}
+
+impl PowerSupply {
+    /// Read a power_supply entry, returns None when it has no type
+    pub fn from_path(path: PathBuf) -> Option<PowerSupply> {
+        let mut supply_type = String::new();
+        sysfs::read(&mut supply_type, &path.join("type")).ok()?;
+
+        let mut online: u8 = 0;
+        sysfs::read(&mut online, &path.join("online")).ok();
+
+        let mut scope = String::new();
+        sysfs::read(&mut scope, &path.join("scope")).ok();
+
+        Some(PowerSupply {
+            name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            supply_type: PowerSupplyType::from_type(&supply_type),
+            online: online == 1,
+            scope,
+            wattage: read_wattage(&path),
+            path,
+        })
+    }
+
+    /// If this supply powers the computer (as opposed to a wireless mouse or similar)
+    pub fn is_system(&self) -> bool {
+        self.scope != "Device"
+    }
+
+    /// If this supply can power the computer from outside, anything that is not a battery
+    pub fn is_external(&self) -> bool {
+        self.supply_type != PowerSupplyType::Battery && self.is_system()
+    }
+}
+
+/// Read the power a supply can deliver in watts
+///
+/// input_power_limit is used when the driver provides it, otherwise current_max × voltage_max.
+/// Both are reported in micro units.
+fn read_wattage(path: &Path) -> Option<f32> {
+    let mut input_power_limit: i64 = 0;
+    if sysfs::read(&mut input_power_limit, &path.join("input_power_limit")).is_ok()
+        && input_power_limit > 0
+    {
+        return Some(input_power_limit as f32 / 1_000_000.0);
+    }
+
+    let mut current_max: i64 = 0;
+    let mut voltage_max: i64 = 0;
+    sysfs::read(&mut current_max, &path.join("current_max")).ok()?;
+    sysfs::read(&mut voltage_max, &path.join("voltage_max")).ok()?;
+
+    if current_max > 0 && voltage_max > 0 {
+        Some((current_max as f64 * voltage_max as f64 / 1e12) as f32)
+    } else {
+        None
+    }
+}
+
+/// The power of a USB Type-C current mode (Type-C current is always at 5V)
+fn typec_mode_wattage(mode: &str) -> Option<f32> {
+    match mode {
+        "default" => Some(2.5),
+        "1.5A" => Some(7.5),
+        "3.0A" => Some(15.0),
+        // USB Power Delivery is negotiated, the power_supply has to report it
+        _ => None,
+    }
+}
+
+/// If a port is being charged, power_role marks the current role, e.g. "source [sink]"
+fn is_sink(power_role: &str) -> bool {
+    power_role.contains("[sink]") || power_role.trim() == "sink"
+}
+
+/// Find the highest power of any connected USB Type-C port from its power_operation_mode
+///
+/// Ports where the computer powers the device that is plugged in are left out
+pub fn typec_wattage_in(parent_path: &Path) -> Option<f32> {
+    let mut wattage: Option<f32> = None;
+
+    for entry in read_dir(parent_path).ok()?.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        // Only ports, not their partners or cables, and only ports with something plugged in
+        if !name.starts_with("port") || name.contains('-') {
+            continue;
+        }
+        if !parent_path.join(format!("{}-partner", name)).exists() {
+            continue;
+        }
+
+        let mut power_role = String::new();
+        sysfs::read(&mut power_role, &entry.path().join("power_role")).ok();
+        if !is_sink(&power_role) {
+            continue;
+        }
+
+        let mut mode = String::new();
+        if sysfs::read(&mut mode, &entry.path().join("power_operation_mode")).is_err() {
+            continue;
+        }
+
+        if let Some(w) = typec_mode_wattage(&mode) {
+            wattage = Some(wattage.map_or(w, |cur: f32| cur.max(w)));
+        }
+    }
+
+    wattage
+}
+
+/// The highest wattage of any online external power supply
+///
+/// None when any online supply does not report its wattage, which is the case for most AC
+/// adapters, so that a full power adapter is never mistaken for the small one next to it.
+pub fn adapter_wattage(supplies: &[PowerSupply]) -> Option<f32> {
+    supplies
+        .iter()
+        .filter(|s| s.is_external() && s.online)
+        .map(|s| s.wattage)
+        .collect::<Option<Vec<f32>>>()?
+        .into_iter()
+        .reduce(f32::max)
+}
+
+/// Enumerate every power_supply within a directory, sorted by name
+pub fn list_power_supplies_in(parent_path: &Path) -> Result<Vec<PowerSupply>, Error> {
+    let mut supplies = Vec::<PowerSupply>::new();
+
+    for entry in read_dir(parent_path)? {
+        if let Some(supply) = PowerSupply::from_path(entry?.path()) {
+            supplies.push(supply);
         }
     }
 
-    None
+    supplies.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(supplies)
+}
+
+/// Enumerate every power_supply on this system
+pub fn list_power_supplies() -> Vec<PowerSupply> {
+    list_power_supplies_in(Path::new(SYSFS_POWER_SUPPLY_PATH)).unwrap_or_default()
+}
+
+/// If any external power supply is online, None when there are no external power supplies
+pub fn external_power_online(supplies: &[PowerSupply]) -> Option<bool> {
+    let mut external = supplies.iter().filter(|s| s.is_external()).peekable();
+    external.peek()?;
+    Some(external.any(|s| s.online))
 }
 
 pub struct Power {
-    pub best_path: &'static str,
-    found_path: bool,
+    parent_path: PathBuf,
 }
 
 pub trait PowerRetriever {
     fn new() -> Self;
     fn read_power_source(&self) -> Result<bool, Error>;
+    fn read_adapter_wattage(&self) -> Option<f32>;
+    fn supplies(&self) -> Vec<PowerSupply>;
+}
+
+impl Power {
+    /// Read power supplies from a directory other than /sys/class/power_supply
+    pub fn from_parent_path(parent_path: &Path) -> Power {
+        Power {
+            parent_path: parent_path.to_path_buf(),
+        }
+    }
 }
 
 impl PowerRetriever for Power {
     fn new() -> Self {
-        // If a path for the AC power_supply is found
-        // store it as found_path so that it does not have to be found
-        // multiple times
-        if let Some(path) = set_best_path() {
-            Power {
-                best_path: path,
-                found_path: true,
-            }
-        } else {
-            Power {
-                best_path: "",
-                found_path: false,
+        Power::from_parent_path(Path::new(SYSFS_POWER_SUPPLY_PATH))
+    }
+
+    /// Returns true when any external power supply (AC, USB-C, UPS...) is online
+    ///
+    /// Power supplies are enumerated every time because USB-C adapters can come and go
+    fn read_power_source(&self) -> Result<bool, Error> {
+        external_power_online(&self.supplies()).ok_or(Error::HdwNotFound)
+    }
+
+    /// Returns the wattage of the connected adapter, None when it is not known
+    ///
+    /// USB supplies that do not report their wattage fall back to the USB Type-C current mode
+    fn read_adapter_wattage(&self) -> Option<f32> {
+        let mut supplies = self.supplies();
+        let unknown_usb = |s: &PowerSupply| {
+            s.is_external()
+                && s.online
+                && s.supply_type == PowerSupplyType::USB
+                && s.wattage.is_none()
+        };
+
+        if supplies.iter().any(unknown_usb) {
+            let typec = typec_wattage_in(Path::new(SYSFS_TYPEC_PATH));
+            for supply in supplies.iter_mut().filter(|s| unknown_usb(s)) {
+                supply.wattage = typec;
             }
         }
+
+        adapter_wattage(&supplies)
     }
 
-    fn read_power_source(&self) -> Result<bool, Error> {
-        if !self.found_path {
-            return Err(Error::HdwNotFound);
+    fn supplies(&self) -> Vec<PowerSupply> {
+        list_power_supplies_in(&self.parent_path).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn make_supply(parent: &Path, name: &str, supply_type: &str, online: Option<&str>) {
+        let path = parent.join(name);
+        fs::create_dir_all(&path).unwrap();
+        fs::write(path.join("type"), format!("{}\n", supply_type)).unwrap();
+        if let Some(online) = online {
+            fs::write(path.join("online"), format!("{}\n", online)).unwrap();
         }
+    }
+
+    #[test]
+    fn read_power_source_unit_test() -> Result<(), Error> {
+        let parent = std::env::temp_dir().join("acs_read_power_source_unit_test");
+        fs::remove_dir_all(&parent).ok();
+
+        // No external supplies at all
+        make_supply(&parent, "BAT0", "Battery", None);
+        let power = Power::from_parent_path(&parent);
+        assert!(matches!(power.read_power_source(), Err(Error::HdwNotFound)));
+
+        // A USB-C adapter is found even though it is not named AC
+        make_supply(&parent, "ADP1", "Mains", Some("0"));
+        make_supply(&parent, "ucsi-source-psy-USBC000:001", "USB", Some("1"));
+        assert!(power.read_power_source()?);
+
+        let supplies = power.supplies();
+        assert_eq!(supplies.len(), 3);
+        assert_eq!(supplies[0].name, "ADP1");
+        assert_eq!(supplies[1].supply_type, PowerSupplyType::Battery);
+        assert_eq!(supplies[2].supply_type, PowerSupplyType::USB);
+
+        make_supply(&parent, "ucsi-source-psy-USBC000:001", "USB", Some("0"));
+        assert!(!power.read_power_source()?);
+
+        fs::remove_dir_all(&parent)?;
+        Ok(())
+    }
+
+    #[test]
+    fn read_adapter_wattage_unit_test() -> Result<(), Error> {
+        let parent = std::env::temp_dir().join("acs_read_adapter_wattage_unit_test");
+        fs::remove_dir_all(&parent).ok();
+
+        // Regular AC adapters do not report their wattage
+        make_supply(&parent, "AC", "Mains", Some("1"));
+        let power = Power::from_parent_path(&parent);
+        assert_eq!(power.read_adapter_wattage(), None);
+
+        // A 15W phone charger, 3A at 5V
+        let usb = parent.join("ucsi-source-psy-USBC000:001");
+        make_supply(&parent, "ucsi-source-psy-USBC000:001", "USB", Some("1"));
+        fs::write(usb.join("current_max"), "3000000\n")?;
+        fs::write(usb.join("voltage_max"), "5000000\n")?;
+        // The AC adapter next to it is still unknown, so it could be full power
+        assert_eq!(power.read_adapter_wattage(), None);
+
+        make_supply(&parent, "AC", "Mains", Some("0"));
+        assert_eq!(power.read_adapter_wattage(), Some(15.0));
+
+        // input_power_limit is preferred
+        fs::write(usb.join("input_power_limit"), "65000000\n")?;
+        assert_eq!(power.read_adapter_wattage(), Some(65.0));
+
+        fs::remove_dir_all(&parent)?;
+        Ok(())
+    }
+
+    #[test]
+    fn typec_wattage_unit_test() -> Result<(), Error> {
+        let parent = std::env::temp_dir().join("acs_typec_wattage_unit_test");
+        fs::remove_dir_all(&parent).ok();
+
+        fs::create_dir_all(parent.join("port0"))?;
+        fs::write(parent.join("port0/power_operation_mode"), "3.0A\n")?;
+        fs::write(parent.join("port0/power_role"), "source [sink]\n")?;
+        fs::create_dir_all(parent.join("port1"))?;
+        fs::write(parent.join("port1/power_operation_mode"), "1.5A\n")?;
+        fs::write(parent.join("port1/power_role"), "[sink] source\n")?;
+        // The computer is charging a phone on this port
+        fs::create_dir_all(parent.join("port2"))?;
+        fs::write(parent.join("port2/power_operation_mode"), "3.0A\n")?;
+        fs::write(parent.join("port2/power_role"), "[source] sink\n")?;
+        fs::create_dir_all(parent.join("port2-partner"))?;
+
+        // Nothing is plugged in
+        assert_eq!(typec_wattage_in(&parent), None);
 
-        let mut pwr_str = fs::read_to_string(self.best_path)?;
+        fs::create_dir_all(parent.join("port1-partner"))?;
+        assert_eq!(typec_wattage_in(&parent), Some(7.5));
 
-        // Remove the \n char
-        pwr_str.pop();
+        fs::create_dir_all(parent.join("port0-partner"))?;
+        assert_eq!(typec_wattage_in(&parent), Some(15.0));
 
-        Ok(pwr_str == "1")
+        fs::remove_dir_all(&parent)?;
+        Ok(())
     }
 }
@@ -1,22 +1,28 @@
 #![forbid(unsafe_code)]
 use crate::error::Error;
+use crate::power::charge_limit::{ChargeControl, ChargeThresholds};
+use crate::power::{list_power_supplies_in, PowerSupplyType};
 use crate::sysfs;
+use serde::Serialize;
 use std::any::Any;
-use std::fs::read_dir;
+use std::fmt;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
 
 // SYSFS root path
 const SYSFS_BATTERY_PATH: &str = "/sys/class/power_supply/";
 
+/// How much of each new power draw reading is mixed into the smoothed power draw
+const POWER_DRAW_SMOOTHING: f32 = 0.2;
+
 /// Returns if this system has a battery or not
 pub fn has_battery() -> bool {
-    let power_dir = Path::new("/sys/class/power_supply/");
-    let dir_count = read_dir(power_dir).into_iter().len();
-    dir_count > 0
+    Batteries::new().is_ok()
 }
 
 /// Describes how the battery condition was obtained
-#[derive(Clone, Default)]
+#[derive(Clone, Default, Serialize)]
 pub enum BatteryConditionType {
     Energy,
     Charge,
@@ -25,11 +31,13 @@ pub enum BatteryConditionType {
 }
 
 /// Describes the current status of the battery
-#[derive(PartialEq, Eq, Clone, Default)]
+#[derive(PartialEq, Eq, Clone, Default, Serialize)]
 pub enum BatteryStatus {
     Charging,
     Discharging,
     Full,
+    /// Plugged in but not charging, usually because a charge threshold has been reached
+    NotCharging,
     #[default]
     Unknown,
 }
@@ -37,8 +45,19 @@ pub enum BatteryStatus {
 /// A structure for holding information about a battery
 /// This structure follows an update model where information within the structure gets updated upon
 /// calling the update method
-#[derive(Clone, Default)]
+#[derive(Clone, Default, Serialize)]
 pub struct Battery {
+    /// The name of the power_supply node, e.g. BAT0
+    pub name: String,
+    pub manufacturer: String,
+    pub model_name: String,
+    pub serial_number: String,
+    /// The chemistry of the battery, e.g. Li-ion
+    pub technology: String,
+    /// Health as reported by the driver, e.g. Good, few drivers report this
+    pub health: String,
+    /// The lowest voltage the battery was designed for in µV
+    pub voltage_min_design: i32,
     pub sys_parent_path: PathBuf,
     pub capacity: i8,
     pub condition_type: BatteryConditionType,
@@ -47,6 +66,22 @@ pub struct Battery {
     pub charge_full_design: i32,
     pub energy_full: i32,
     pub energy_full_design: i32,
+    /// Energy left in µWh
+    pub energy_now: i32,
+    /// Charge left in µAh
+    pub charge_now: i32,
+    /// Power going in or out of the battery in µW
+    pub power_now: i32,
+    /// Current going in or out of the battery in µA
+    pub current_now: i32,
+    /// Voltage of the battery in µV
+    pub voltage_now: i32,
+    /// How many times the battery has been fully charged and discharged, 0 when not reported
+    pub cycle_count: i32,
+    /// Power going in or out of the battery in watts, from the last update
+    pub power_draw: f32,
+    /// Power going in or out of the battery in watts, averaged over many updates
+    pub power_draw_smoothed: f32,
     pub status: BatteryStatus,
 }
 
@@ -56,7 +91,6 @@ impl Battery {
     /// current system. It will also initialize the condition_type variable by checking in the file
     /// system.
     pub fn new() -> Result<Battery, Error> {
-        let mut obj = Battery::default();
         let path: PathBuf = match sysfs::get_path_by_glob(SYSFS_BATTERY_PATH, "BAT*") {
             Ok(path) => path,
             Err(error) => {
@@ -67,9 +101,101 @@ impl Battery {
                 return Err(Error::HdwNotFound);
             }
         };
-        obj.sys_parent_path = path;
+        Ok(Battery::from_path(path))
+    }
+
+    /// Creates a new instance of a battery for a known power_supply path, e.g.
+    /// /sys/class/power_supply/BAT1
+    pub fn from_path(path: PathBuf) -> Battery {
+        let mut obj = Battery {
+            name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            sys_parent_path: path,
+            ..Default::default()
+        };
         obj.check_condition_type();
-        Ok(obj)
+        obj.read_info();
+        obj
+    }
+
+    /// Reads the values that describe the battery and never change
+    fn read_info(&mut self) {
+        self.manufacturer = self.read_optional("manufacturer");
+        self.model_name = self.read_optional("model_name");
+        self.serial_number = self.read_optional("serial_number");
+        self.technology = self.read_optional("technology");
+        self.voltage_min_design = self.read_optional("voltage_min_design");
+    }
+
+    /// Every attribute this battery reports as a key, value and unit
+    ///
+    /// Values the driver does not provide are left out. Energy is in Wh, charge in Ah and voltage
+    /// in V.
+    pub fn attributes(&self) -> Vec<(&'static str, String, &'static str)> {
+        let mut attributes = Vec::<(&'static str, String, &'static str)>::new();
+
+        let text = [
+            ("manufacturer", &self.manufacturer),
+            ("model_name", &self.model_name),
+            ("serial_number", &self.serial_number),
+            ("technology", &self.technology),
+            ("health", &self.health),
+        ];
+        for (key, value) in text {
+            if !value.is_empty() {
+                attributes.push((key, value.clone(), ""));
+            }
+        }
+
+        attributes.push(("status", self.status.to_string(), ""));
+        attributes.push(("capacity", self.capacity.to_string(), "%"));
+        if self.condition > 0 {
+            attributes.push(("condition", self.condition.to_string(), "%"));
+        }
+        if self.cycle_count > 0 {
+            attributes.push(("cycle_count", self.cycle_count.to_string(), ""));
+        }
+
+        // Values in micro units
+        let micro = [
+            ("voltage_min_design", self.voltage_min_design, "V"),
+            ("voltage_now", self.voltage_now, "V"),
+            ("energy_now", self.energy_now, "Wh"),
+            ("energy_full", self.energy_full, "Wh"),
+            ("energy_full_design", self.energy_full_design, "Wh"),
+            ("charge_now", self.charge_now, "Ah"),
+            ("charge_full", self.charge_full, "Ah"),
+            ("charge_full_design", self.charge_full_design, "Ah"),
+        ];
+        for (key, value, unit) in micro {
+            if value > 0 {
+                attributes.push((key, format!("{:.2}", value as f32 / 1_000_000.0), unit));
+            }
+        }
+
+        if self.power_draw > 0.0 {
+            attributes.push(("power_draw", format!("{:.2}", self.power_draw), "W"));
+        }
+
+        attributes
+    }
+
+    /// The value used to weigh this battery against others when combining capacities
+    /// Batteries that do not report how much they can hold are weighed equally
+    fn weight(&self) -> f32 {
+        let full = match self.condition_type {
+            BatteryConditionType::Energy => self.energy_full,
+            BatteryConditionType::Charge => self.charge_full,
+            BatteryConditionType::None => 0,
+        };
+
+        if full > 0 {
+            full as f32
+        } else {
+            1.0
+        }
     }
 
     /// Get the battery charge on this device then updates the struct
@@ -82,6 +208,110 @@ impl Battery {
         Ok(())
     }
 
+    /// Read a value that only some battery drivers provide, missing values are left as default
+    fn read_optional<T>(&self, name: &str) -> T
+    where
+        T: FromStr + Default,
+        <T as std::str::FromStr>::Err: std::fmt::Debug,
+    {
+        let mut value = T::default();
+        sysfs::read(&mut value, &self.sys_parent_path.clone().join(name))
+            .map(|_| value)
+            .unwrap_or_default()
+    }
+
+    /// Get whether the battery is charging or discharging then updates the struct
+    fn read_status(&mut self) {
+        let mut status = String::new();
+        self.status = match sysfs::read(&mut status, &self.sys_parent_path.clone().join("status")) {
+            Ok(_) => match status.as_str() {
+                "Charging" => BatteryStatus::Charging,
+                "Discharging" => BatteryStatus::Discharging,
+                "Full" => BatteryStatus::Full,
+                "Not charging" => BatteryStatus::NotCharging,
+                _ => BatteryStatus::Unknown,
+            },
+            Err(_) => BatteryStatus::Unknown,
+        };
+    }
+
+    /// Reads how much energy is left and how fast it is changing then updates the struct
+    ///
+    /// Drivers report either power_now or current_now and voltage_now, and either energy_now or
+    /// charge_now, so every value is optional.
+    fn read_power_draw(&mut self) {
+        self.energy_now = self.read_optional("energy_now");
+        self.charge_now = self.read_optional("charge_now");
+        self.power_now = self.read_optional("power_now");
+        self.current_now = self.read_optional("current_now");
+        self.voltage_now = self.read_optional("voltage_now");
+
+        // Some drivers report a negative rate when discharging
+        self.power_draw = if self.power_now != 0 {
+            self.power_now.abs() as f32 / 1_000_000.0
+        } else {
+            (self.current_now.abs() as f32 / 1_000_000.0) * (self.voltage_now as f32 / 1_000_000.0)
+        };
+
+        self.power_draw_smoothed = if self.power_draw_smoothed == 0.0 {
+            self.power_draw
+        } else {
+            self.power_draw_smoothed * (1.0 - POWER_DRAW_SMOOTHING)
+                + self.power_draw * POWER_DRAW_SMOOTHING
+        };
+    }
+
+    /// The energy left in the battery in watt hours
+    pub fn energy_now_wh(&self) -> f32 {
+        if self.energy_now > 0 {
+            self.energy_now as f32 / 1_000_000.0
+        } else {
+            (self.charge_now as f32 / 1_000_000.0) * (self.voltage_now as f32 / 1_000_000.0)
+        }
+    }
+
+    /// The energy the battery can hold in watt hours
+    pub fn energy_full_wh(&self) -> f32 {
+        if self.energy_full > 0 {
+            self.energy_full as f32 / 1_000_000.0
+        } else {
+            (self.charge_full as f32 / 1_000_000.0) * (self.voltage_now as f32 / 1_000_000.0)
+        }
+    }
+
+    /// The energy the battery could hold when new in watt hours
+    pub fn energy_full_design_wh(&self) -> f32 {
+        if self.energy_full_design > 0 {
+            self.energy_full_design as f32 / 1_000_000.0
+        } else {
+            (self.charge_full_design as f32 / 1_000_000.0) * (self.voltage_now as f32 / 1_000_000.0)
+        }
+    }
+
+    /// How charge thresholds can be controlled for this battery, if at all
+    pub fn charge_control(&self) -> Option<ChargeControl> {
+        ChargeControl::find(&self.sys_parent_path)
+    }
+
+    /// Estimated time until this battery is empty, only known while discharging
+    pub fn time_to_empty(&self) -> Option<Duration> {
+        if self.status != BatteryStatus::Discharging {
+            return None;
+        }
+        estimate_time(self.energy_now_wh(), self.power_draw_smoothed)
+    }
+
+    /// Estimated time until this battery is full, only known while charging
+    pub fn time_to_full(&self) -> Option<Duration> {
+        if self.status != BatteryStatus::Charging {
+            return None;
+        }
+        estimate_time(
+            (self.energy_full_wh() - self.energy_now_wh()).max(0.0),
+            self.power_draw_smoothed,
+        )
+    }
+
     /// Checks the file system for the proper battery condition type for this system then updates
     /// the struct
     /// BatteryConditionType::Charge = charge_full
@@ -111,9 +341,8 @@ impl Battery {
                 self.condition =
                     ((self.charge_full as f32 / self.charge_full_design as f32) * 100_f32) as i8
             }
-            BatteryConditionType::None => {
-                return Err(Error::Unknown);
-            }
+            // Some drivers do not report how much the battery can hold, 0 means unknown
+            BatteryConditionType::None => self.condition = 0,
         }
 
         Ok(())
@@ -149,9 +378,448 @@ impl Battery {
 
     /// Updates all values in this struct from the battery drivers
     pub fn update(&mut self) -> Result<(), Error> {
-        self.get_condition()?;
         self.read_charge()?;
+        self.read_status();
+        self.read_power_draw();
+        self.cycle_count = self.read_optional("cycle_count");
+        self.health = self.read_optional("health");
+        self.get_condition()?;
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for BatteryStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BatteryStatus::Charging => write!(f, "charging"),
+            BatteryStatus::Discharging => write!(f, "discharging"),
+            BatteryStatus::Full => write!(f, "full"),
+            BatteryStatus::NotCharging => write!(f, "not charging"),
+            BatteryStatus::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// Every battery found on the system along with their combined values
+///
+/// Laptops such as some ThinkPads have more than one battery (BAT0 and BAT1). The combined
+/// capacity is weighted by how much each battery can hold so that a small internal battery does
+/// not count as much as a large external one.
+#[derive(Clone, Default)]
+pub struct Batteries {
+    pub batteries: Vec<Battery>,
+    /// Combined charge of every battery in percent
+    pub capacity: i8,
+    /// Combined condition of every battery in percent
+    pub condition: i8,
+    /// Combined status of every battery
+    pub status: BatteryStatus,
+}
+
+impl Batteries {
+    /// Finds every battery on this system
+    pub fn new() -> Result<Batteries, Error> {
+        Batteries::from_parent_path(SYSFS_BATTERY_PATH)
+    }
+
+    /// Finds every battery within a power_supply directory
+    ///
+    /// Batteries are found by their type, so batteries not named BAT* are found as well. The
+    /// batteries of devices such as wireless mice are left out.
+    pub fn from_parent_path(parent_path: &str) -> Result<Batteries, Error> {
+        let batteries: Vec<Battery> = list_power_supplies_in(Path::new(parent_path))?
+            .into_iter()
+            .filter(|s| s.supply_type == PowerSupplyType::Battery && s.is_system())
+            .map(|s| Battery::from_path(s.path))
+            .collect();
+
+        if batteries.is_empty() {
+            return Err(Error::HdwNotFound);
+        }
+
+        Ok(Batteries {
+            batteries,
+            ..Default::default()
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.batteries.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Battery> {
+        self.batteries.iter()
+    }
+
+    /// Updates every battery and then the combined values
+    /// Every battery gets updated even if one of them fails, the first error is returned
+    pub fn update(&mut self) -> Result<(), Error> {
+        let mut result = Ok(());
+
+        for battery in self.batteries.iter_mut() {
+            if let Err(e) = battery.update() {
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+
+        self.combine();
+        result
+    }
+
+    /// If the batteries say the system is on external power, None when it can not be told
+    pub fn on_external_power(&self) -> Option<bool> {
+        match self.status {
+            BatteryStatus::Charging | BatteryStatus::Full | BatteryStatus::NotCharging => {
+                Some(true)
+            }
+            BatteryStatus::Discharging => Some(false),
+            BatteryStatus::Unknown => None,
+        }
+    }
+
+    /// Set the charge thresholds of every battery
+    ///
+    /// Vendor specific controls apply to the whole laptop so they only get written once. Returns
+    /// each control that was written to.
+    pub fn set_charge_thresholds(
+        &self,
+        thresholds: &ChargeThresholds,
+    ) -> Result<Vec<ChargeControl>, Error> {
+        let mut controls = Vec::<ChargeControl>::new();
+        for battery in self.batteries.iter() {
+            if let Some(control) = battery.charge_control() {
+                if !controls.contains(&control) {
+                    controls.push(control);
+                }
+            }
+        }
+
+        if controls.is_empty() {
+            return Err(Error::HdwNotFound);
+        }
+
+        for control in controls.iter() {
+            control.write(thresholds)?;
+        }
+
+        Ok(controls)
+    }
+
+    /// Power going in or out of every battery in watts, from the last update
+    pub fn power_draw(&self) -> f32 {
+        self.batteries.iter().map(|b| b.power_draw).sum()
+    }
+
+    /// Power going in or out of every battery in watts, averaged over many updates
+    pub fn power_draw_smoothed(&self) -> f32 {
+        self.batteries.iter().map(|b| b.power_draw_smoothed).sum()
+    }
+
+    /// Estimated time until every battery is empty, only known while discharging
+    pub fn time_to_empty(&self) -> Option<Duration> {
+        if self.status != BatteryStatus::Discharging {
+            return None;
+        }
+
+        let energy: f32 = self.batteries.iter().map(|b| b.energy_now_wh()).sum();
+        estimate_time(energy, self.power_draw_smoothed())
+    }
+
+    /// Estimated time until every battery is full, only known while charging
+    pub fn time_to_full(&self) -> Option<Duration> {
+        if self.status != BatteryStatus::Charging {
+            return None;
+        }
+
+        let energy: f32 = self
+            .batteries
+            .iter()
+            .map(|b| (b.energy_full_wh() - b.energy_now_wh()).max(0.0))
+            .sum();
+        estimate_time(energy, self.power_draw_smoothed())
+    }
+
+    /// Calculates the combined capacity, condition and status from each battery
+    fn combine(&mut self) {
+        self.capacity = combined_capacity(&self.batteries);
+        self.condition = combined_condition(&self.batteries);
+        self.status = combined_status(&self.batteries);
+    }
+}
+
+/// Estimate how long it takes to move an amount of energy (Wh) at a rate (W)
+pub fn estimate_time(energy_wh: f32, power_w: f32) -> Option<Duration> {
+    if power_w <= 0.0 || energy_wh <= 0.0 {
+        return None;
+    }
+
+    Some(Duration::from_secs_f32(energy_wh / power_w * 3600.0))
+}
+
+/// Combine the capacity of many batteries, weighted by how much each can hold
+pub fn combined_capacity(batteries: &[Battery]) -> i8 {
+    let total_weight: f32 = batteries.iter().map(|b| b.weight()).sum();
+    if total_weight == 0.0 {
+        return 0;
+    }
+
+    let weighted: f32 = batteries
+        .iter()
+        .map(|b| b.capacity as f32 * b.weight())
+        .sum();
+
+    (weighted / total_weight).round() as i8
+}
+
+/// Combine the condition of many batteries, weighted by how much each can hold
+pub fn combined_condition(batteries: &[Battery]) -> i8 {
+    let total_weight: f32 = batteries.iter().map(|b| b.weight()).sum();
+    if total_weight == 0.0 {
+        return 0;
+    }
+
+    let weighted: f32 = batteries
+        .iter()
+        .map(|b| b.condition as f32 * b.weight())
+        .sum();
+
+    (weighted / total_weight).round() as i8
+}
+
+/// Combine the status of many batteries
+///
+/// If any battery is charging the system is charging, otherwise if any battery is discharging the
+/// system is discharging. Only when every battery is full is the system full.
+pub fn combined_status(batteries: &[Battery]) -> BatteryStatus {
+    if batteries.is_empty() {
+        return BatteryStatus::Unknown;
+    }
+
+    if batteries
+        .iter()
+        .any(|b| b.status == BatteryStatus::Charging)
+    {
+        return BatteryStatus::Charging;
+    }
+
+    if batteries
+        .iter()
+        .any(|b| b.status == BatteryStatus::Discharging)
+    {
+        return BatteryStatus::Discharging;
+    }
+
+    if batteries.iter().all(|b| b.status == BatteryStatus::Full) {
+        return BatteryStatus::Full;
+    }
+
+    // Some batteries may be full while others are held at their charge threshold
+    if batteries
+        .iter()
+        .all(|b| b.status == BatteryStatus::Full || b.status == BatteryStatus::NotCharging)
+    {
+        return BatteryStatus::NotCharging;
+    }
+
+    BatteryStatus::Unknown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv::Writable;
+
+    fn battery(capacity: i8, energy_full: i32, status: BatteryStatus) -> Battery {
+        Battery {
+            capacity,
+            energy_full,
+            condition_type: BatteryConditionType::Energy,
+            status,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn combined_capacity_unit_test() {
+        // A large battery at 100% and a small one at 10%
+        let batteries = vec![
+            battery(100, 69_000_000, BatteryStatus::Full),
+            battery(10, 23_000_000, BatteryStatus::Discharging),
+        ];
+        assert_eq!(combined_capacity(&batteries), 78);
+
+        // Same size batteries are averaged
+        let batteries = vec![
+            battery(80, 50_000_000, BatteryStatus::Unknown),
+            battery(40, 50_000_000, BatteryStatus::Unknown),
+        ];
+        assert_eq!(combined_capacity(&batteries), 60);
+
+        // Batteries that do not report energy_full are weighted equally
+        let batteries = vec![
+            battery(80, 0, BatteryStatus::Unknown),
+            battery(20, 0, BatteryStatus::Unknown),
+        ];
+        assert_eq!(combined_capacity(&batteries), 50);
+
+        assert_eq!(combined_capacity(&[]), 0);
+    }
+
+    #[test]
+    fn estimate_time_unit_test() {
+        assert_eq!(
+            estimate_time(50.0, 10.0),
+            Some(Duration::from_secs(5 * 3600))
+        );
+        assert_eq!(estimate_time(5.0, 20.0), Some(Duration::from_secs(15 * 60)));
+        assert_eq!(estimate_time(50.0, 0.0), None);
+        assert_eq!(estimate_time(0.0, 10.0), None);
+    }
+
+    #[test]
+    fn time_to_empty_unit_test() {
+        let mut batteries = Batteries {
+            batteries: vec![
+                Battery {
+                    energy_now: 30_000_000,
+                    power_draw_smoothed: 6.0,
+                    status: BatteryStatus::Discharging,
+                    ..Default::default()
+                },
+                Battery {
+                    energy_now: 15_000_000,
+                    power_draw_smoothed: 3.0,
+                    status: BatteryStatus::Discharging,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        batteries.combine();
+
+        assert_eq!(batteries.power_draw_smoothed(), 9.0);
+        assert_eq!(
+            batteries.time_to_empty(),
+            Some(Duration::from_secs(5 * 3600))
+        );
+        assert_eq!(batteries.time_to_full(), None);
+    }
+
+    #[test]
+    fn battery_to_csv_unit_test() {
+        let battery = Battery {
+            name: "BAT0".to_string(),
+            capacity: 50,
+            energy_now: 20_000_000,
+            power_draw: 10.0,
+            power_draw_smoothed: 10.0,
+            status: BatteryStatus::Discharging,
+            ..Default::default()
+        };
+
+        let out = battery.to_csv();
+        assert!(out.contains(",BAT0,,,,,,,,50,10,7200,\n"));
+    }
+
+    #[test]
+    fn combined_status_unit_test() {
+        let batteries = vec![
+            battery(100, 1, BatteryStatus::Full),
+            battery(50, 1, BatteryStatus::Charging),
+        ];
+        assert!(combined_status(&batteries) == BatteryStatus::Charging);
+
+        let batteries = vec![
+            battery(100, 1, BatteryStatus::Full),
+            battery(50, 1, BatteryStatus::Discharging),
+        ];
+        assert!(combined_status(&batteries) == BatteryStatus::Discharging);
+
+        let batteries = vec![
+            battery(100, 1, BatteryStatus::Full),
+            battery(100, 1, BatteryStatus::Full),
+        ];
+        assert!(combined_status(&batteries) == BatteryStatus::Full);
+
+        let batteries = vec![
+            battery(100, 1, BatteryStatus::Full),
+            battery(80, 1, BatteryStatus::NotCharging),
+        ];
+        assert!(combined_status(&batteries) == BatteryStatus::NotCharging);
+
+        assert!(combined_status(&[]) == BatteryStatus::Unknown);
+    }
+
+    #[test]
+    fn battery_attributes_unit_test() -> Result<(), Error> {
+        let path = std::env::temp_dir().join("acs_battery_attributes_unit_test/BAT0");
+        std::fs::remove_dir_all(&path).ok();
+        std::fs::create_dir_all(&path)?;
+
+        for (name, value) in [
+            ("manufacturer", "SMP"),
+            ("model_name", "5B10W13930"),
+            ("technology", "Li-poly"),
+            ("capacity", "80"),
+            ("status", "Discharging"),
+            ("cycle_count", "312"),
+            ("voltage_min_design", "11520000"),
+            ("energy_now", "40000000"),
+            ("energy_full", "50000000"),
+            ("energy_full_design", "57000000"),
+        ] {
+            std::fs::write(path.join(name), format!("{}\n", value))?;
+        }
+
+        let mut battery = Battery::from_path(path.clone());
+        battery.update()?;
+
+        let attributes = battery.attributes();
+        let find = |key: &str| attributes.iter().find(|a| a.0 == key).map(|a| a.1.clone());
+
+        assert_eq!(find("manufacturer"), Some("SMP".to_string()));
+        assert_eq!(find("status"), Some("discharging".to_string()));
+        assert_eq!(find("cycle_count"), Some("312".to_string()));
+        assert_eq!(find("condition"), Some("87".to_string()));
+        assert_eq!(find("voltage_min_design"), Some("11.52".to_string()));
+        assert_eq!(find("energy_full_design"), Some("57.00".to_string()));
+        // Not reported by this battery
+        assert_eq!(find("serial_number"), None);
+        assert_eq!(find("charge_now"), None);
+
+        std::fs::remove_dir_all(path.parent().unwrap())?;
+        Ok(())
+    }
+
+    #[test]
+    fn battery_without_condition_unit_test() -> Result<(), Error> {
+        let path = std::env::temp_dir().join("acs_battery_without_condition_unit_test/BAT0");
+        std::fs::remove_dir_all(&path).ok();
+        std::fs::create_dir_all(&path)?;
+
+        // No energy_full or charge_full, so the condition is unknown
+        for (name, value) in [
+            ("capacity", "64"),
+            ("status", "Discharging"),
+            ("energy_now", "30000000"),
+        ] {
+            std::fs::write(path.join(name), format!("{}\n", value))?;
+        }
+
+        let mut battery = Battery::from_path(path.clone());
+        battery.update()?;
+
+        let attributes = battery.attributes();
+        let find = |key: &str| attributes.iter().find(|a| a.0 == key).map(|a| a.1.clone());
+
+        assert_eq!(battery.capacity, 64);
+        assert_eq!(find("status"), Some("discharging".to_string()));
+        assert_eq!(find("condition"), None);
 
+        std::fs::remove_dir_all(path.parent().unwrap())?;
         Ok(())
     }
 }
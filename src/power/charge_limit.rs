@@ -0,0 +1,234 @@
+#![forbid(unsafe_code)]
+//! Charge thresholds stop the battery from charging to 100% while on AC power
+//!
+//! Keeping a lithium battery at full charge all day wears it out, so most laptops let the
+//! operating system pick a range to charge between. The kernel exposes this in a standard way on
+//! the battery's power_supply node, but some vendors only expose their own files.
+use crate::error::Error;
+use crate::sysfs;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+const HUAWEI_THRESHOLDS_PATH: &str = "/sys/devices/platform/huawei-wmi/charge_control_thresholds";
+const IDEAPAD_DRIVER_PATH: &str = "/sys/bus/platform/drivers/ideapad_acpi/";
+
+/// The range the battery should charge between in percent
+///
+/// Charging starts when the battery falls below `start` and stops when it reaches `end`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChargeThresholds {
+    pub start: u8,
+    pub end: u8,
+}
+
+impl ChargeThresholds {
+    /// Creates thresholds, making sure they make sense
+    pub fn new(start: u8, end: u8) -> Result<ChargeThresholds, Error> {
+        let thresholds = ChargeThresholds { start, end };
+        thresholds.validate()?;
+        Ok(thresholds)
+    }
+
+    /// Check that the start is below the end and that the end is not above 100%
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.end > 100 || self.end == 0 {
+            return Err(Error::InvalidValue(format!(
+                "charge end threshold must be between 1 and 100, got {}",
+                self.end
+            )));
+        }
+
+        if self.start >= self.end {
+            return Err(Error::InvalidValue(format!(
+                "charge start threshold ({}) must be below the end threshold ({})",
+                self.start, self.end
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for ChargeThresholds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}% - {}%", self.start, self.end)
+    }
+}
+
+/// The way charge thresholds can be controlled on this system
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChargeControl {
+    /// charge_control_start_threshold and charge_control_end_threshold on the battery node
+    /// ASUS laptops only have the end threshold
+    Standard {
+        start: Option<PathBuf>,
+        end: PathBuf,
+    },
+    /// huawei-wmi takes both thresholds in one file
+    Huawei(PathBuf),
+    /// ideapad_acpi can only turn conservation mode on or off, which stops charging at ~60%
+    IdeapadConservation(PathBuf),
+}
+
+impl ChargeControl {
+    /// Find how charge thresholds can be controlled for a battery, checking the standard files
+    /// first and then each vendor specific file
+    pub fn find(battery_path: &Path) -> Option<ChargeControl> {
+        if let Some(control) = ChargeControl::find_standard(battery_path) {
+            return Some(control);
+        }
+
+        let huawei = PathBuf::from(HUAWEI_THRESHOLDS_PATH);
+        if huawei.is_file() {
+            return Some(ChargeControl::Huawei(huawei));
+        }
+
+        if let Ok(paths) = sysfs::get_paths_by_glob(IDEAPAD_DRIVER_PATH, "VPC2004:*") {
+            for path in paths {
+                let conservation = path.join("conservation_mode");
+                if conservation.is_file() {
+                    return Some(ChargeControl::IdeapadConservation(conservation));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Find the standard threshold files on a battery's power_supply node
+    fn find_standard(battery_path: &Path) -> Option<ChargeControl> {
+        let end = battery_path.join("charge_control_end_threshold");
+        if !end.is_file() {
+            return None;
+        }
+
+        let start = battery_path.join("charge_control_start_threshold");
+        Some(ChargeControl::Standard {
+            start: if start.is_file() { Some(start) } else { None },
+            end,
+        })
+    }
+
+    /// Read the thresholds that are currently in use
+    pub fn read(&self) -> Result<ChargeThresholds, Error> {
+        match self {
+            ChargeControl::Standard { start, end } => {
+                let mut thresholds = ChargeThresholds { start: 0, end: 100 };
+                sysfs::read(&mut thresholds.end, end)?;
+                if let Some(start) = start {
+                    sysfs::read(&mut thresholds.start, start)?;
+                }
+                Ok(thresholds)
+            }
+            ChargeControl::Huawei(path) => {
+                let mut value = String::new();
+                sysfs::read(&mut value, path)?;
+                let mut parts = value.split_whitespace().map(|x| x.parse::<u8>());
+                match (parts.next(), parts.next()) {
+                    (Some(Ok(start)), Some(Ok(end))) => Ok(ChargeThresholds { start, end }),
+                    _ => Err(Error::Parse),
+                }
+            }
+            ChargeControl::IdeapadConservation(path) => {
+                let mut enabled: u8 = 0;
+                sysfs::read(&mut enabled, path)?;
+                Ok(if enabled == 1 {
+                    ChargeThresholds { start: 0, end: 60 }
+                } else {
+                    ChargeThresholds { start: 0, end: 100 }
+                })
+            }
+        }
+    }
+
+    /// Write new thresholds
+    ///
+    /// Conservation mode can only be turned on or off, so it gets turned on for any end
+    /// threshold below 100%.
+    pub fn write(&self, thresholds: &ChargeThresholds) -> Result<(), Error> {
+        thresholds.validate()?;
+
+        match self {
+            ChargeControl::Standard { start, end } => {
+                let start = match start {
+                    Some(start) => start,
+                    None => return sysfs::write(end, &thresholds.end.to_string()),
+                };
+
+                // The kernel refuses a start above the current end (or an end below the current
+                // start), so the order of the writes depends on which way the range moves
+                let mut current_end: u8 = 100;
+                sysfs::read(&mut current_end, end)?;
+
+                if thresholds.start >= current_end {
+                    sysfs::write(end, &thresholds.end.to_string())?;
+                    sysfs::write(start, &thresholds.start.to_string())
+                } else {
+                    sysfs::write(start, &thresholds.start.to_string())?;
+                    sysfs::write(end, &thresholds.end.to_string())
+                }
+            }
+            ChargeControl::Huawei(path) => {
+                sysfs::write(path, &format!("{} {}", thresholds.start, thresholds.end))
+            }
+            ChargeControl::IdeapadConservation(path) => {
+                sysfs::write(path, if thresholds.end < 100 { "1" } else { "0" })
+            }
+        }
+    }
+}
+
+impl fmt::Display for ChargeControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChargeControl::Standard { start: Some(_), .. } => {
+                write!(f, "charge control thresholds")
+            }
+            ChargeControl::Standard { start: None, .. } => {
+                write!(f, "charge control end threshold")
+            }
+            ChargeControl::Huawei(_) => write!(f, "huawei-wmi thresholds"),
+            ChargeControl::IdeapadConservation(_) => write!(f, "ideapad conservation mode"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn charge_thresholds_validate_unit_test() {
+        assert!(ChargeThresholds::new(60, 80).is_ok());
+        assert!(ChargeThresholds::new(0, 100).is_ok());
+        assert!(ChargeThresholds::new(80, 60).is_err());
+        assert!(ChargeThresholds::new(80, 80).is_err());
+        assert!(ChargeThresholds::new(40, 101).is_err());
+    }
+
+    #[test]
+    fn standard_charge_control_unit_test() -> Result<(), Error> {
+        let battery = std::env::temp_dir().join("acs_charge_limit_unit_test/BAT0");
+        fs::create_dir_all(&battery)?;
+        fs::write(battery.join("charge_control_start_threshold"), "0\n")?;
+        fs::write(battery.join("charge_control_end_threshold"), "50\n")?;
+
+        let control = ChargeControl::find(&battery).ok_or(Error::HdwNotFound)?;
+        assert!(matches!(
+            control,
+            ChargeControl::Standard { start: Some(_), .. }
+        ));
+
+        // Moving the range above the current end threshold
+        control.write(&ChargeThresholds::new(60, 80)?)?;
+        assert_eq!(control.read()?, ChargeThresholds { start: 60, end: 80 });
+
+        control.write(&ChargeThresholds::new(20, 40)?)?;
+        assert_eq!(control.read()?, ChargeThresholds { start: 20, end: 40 });
+
+        fs::remove_dir_all(battery.parent().unwrap())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,278 @@
+#![forbid(unsafe_code)]
+//! Battery health history
+//!
+//! The daemon records the condition and cycle count of each battery once a day to
+//! /var/lib/acs/battery_history.jsonl, one JSON entry per line. This shows how fast a battery is
+//! wearing out, which `acs get battery --history` displays.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::Error;
+use crate::power::battery::{Batteries, Battery};
+
+pub const HISTORY_FILE: &str = "/var/lib/acs/battery_history.jsonl";
+
+/// How often a battery gets recorded
+pub const RECORD_INTERVAL_SECS: u64 = 24 * 60 * 60;
+
+/// The most entries kept in the file, about a year for three batteries
+const MAX_ENTRIES: usize = 1000;
+
+const SECS_PER_MONTH: f32 = 30.0 * 24.0 * 60.0 * 60.0;
+
+/// The health of one battery at one moment
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct HistoryEntry {
+    /// Seconds since the unix epoch
+    pub timestamp: u64,
+    /// The name of the battery, e.g. BAT0
+    pub battery: String,
+    /// 0 when the battery does not report its cycle count
+    pub cycle_count: i32,
+    /// What the battery can hold now in watt hours
+    pub full_wh: f32,
+    /// What the battery could hold when new in watt hours
+    pub design_wh: f32,
+    /// full as a percent of design
+    pub condition: i8,
+}
+
+impl HistoryEntry {
+    pub fn from_battery(battery: &Battery, timestamp: u64) -> HistoryEntry {
+        HistoryEntry {
+            timestamp,
+            battery: battery.name.clone(),
+            cycle_count: battery.cycle_count,
+            full_wh: battery.energy_full_wh(),
+            design_wh: battery.energy_full_design_wh(),
+            condition: battery.condition,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BatteryHistory {
+    pub entries: Vec<HistoryEntry>,
+}
+
+impl BatteryHistory {
+    /// Read the history from a file, a missing file is an empty history
+    pub fn load_from(path: &Path) -> Result<BatteryHistory, Error> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(Error::IO(e)),
+        };
+
+        Ok(BatteryHistory {
+            // A damaged line should not lose the rest of the history
+            entries: contents
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect(),
+        })
+    }
+
+    pub fn load() -> Result<BatteryHistory, Error> {
+        BatteryHistory::load_from(Path::new(HISTORY_FILE))
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), Error> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Write the whole history first so a crash can not leave it half written
+        let temp = path.with_extension("jsonl.tmp");
+        let mut file = File::create(&temp)?;
+
+        for entry in &self.entries {
+            let line = serde_json::to_string(entry).map_err(|_| Error::Parse)?;
+            writeln!(file, "{}", line)?;
+        }
+        drop(file);
+
+        fs::rename(&temp, path)?;
+        Ok(())
+    }
+
+    /// Add an entry for a battery if it has not been recorded within the record interval
+    ///
+    /// Returns true if an entry was added
+    pub fn record(&mut self, battery: &Battery, timestamp: u64) -> bool {
+        // A condition of 0 means it could not be read
+        if battery.condition <= 0 {
+            return false;
+        }
+
+        let last = self
+            .entries
+            .iter()
+            .rev()
+            .find(|entry| entry.battery == battery.name);
+
+        if let Some(last) = last {
+            if timestamp < last.timestamp + RECORD_INTERVAL_SECS {
+                return false;
+            }
+        }
+
+        self.entries
+            .push(HistoryEntry::from_battery(battery, timestamp));
+
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.drain(..self.entries.len() - MAX_ENTRIES);
+        }
+
+        true
+    }
+
+    /// The names of every battery in the history in the order they first appear
+    pub fn battery_names(&self) -> Vec<String> {
+        let mut names = Vec::<String>::new();
+        for entry in &self.entries {
+            if !names.contains(&entry.battery) {
+                names.push(entry.battery.clone());
+            }
+        }
+        names
+    }
+
+    /// Every entry of one battery, oldest first
+    pub fn for_battery(&self, name: &str) -> Vec<&HistoryEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.battery == name)
+            .collect()
+    }
+}
+
+/// Record every battery to the history file if they are due
+///
+/// Returns how many entries were added
+pub fn record_batteries(batteries: &Batteries) -> Result<usize, Error> {
+    let path = Path::new(HISTORY_FILE);
+    let mut history = BatteryHistory::load_from(path)?;
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+    let recorded = batteries
+        .iter()
+        .filter(|battery| history.record(battery, timestamp))
+        .count();
+
+    if recorded > 0 {
+        history.save_to(path)?;
+    }
+
+    Ok(recorded)
+}
+
+/// How many percent of condition is lost each month, using a least squares fit over every entry
+///
+/// None when the entries span less than a day
+pub fn wear_rate_per_month(entries: &[&HistoryEntry]) -> Option<f32> {
+    let first = entries.first()?;
+    let last = entries.last()?;
+    if last.timestamp < first.timestamp + RECORD_INTERVAL_SECS {
+        return None;
+    }
+
+    // Timestamps are made relative to the first entry so they fit in an f32
+    let points: Vec<(f32, f32)> = entries
+        .iter()
+        .map(|e| {
+            (
+                (e.timestamp - first.timestamp) as f32 / SECS_PER_MONTH,
+                e.condition as f32,
+            )
+        })
+        .collect();
+
+    let n = points.len() as f32;
+    let mean_x = points.iter().map(|p| p.0).sum::<f32>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f32>() / n;
+
+    let covariance: f32 = points.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+    let variance: f32 = points.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+
+    if variance == 0.0 {
+        return None;
+    }
+
+    // Condition goes down as the battery wears, so a falling slope is a positive wear rate
+    Some(-covariance / variance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_battery(name: &str, condition: i8) -> Battery {
+        Battery {
+            name: name.to_string(),
+            condition,
+            cycle_count: 100,
+            energy_full: condition as i32 * 500_000,
+            energy_full_design: 50_000_000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn record_history_unit_test() -> Result<(), Error> {
+        let mut history = BatteryHistory::default();
+
+        assert!(history.record(&make_battery("BAT0", 95), 0));
+        // Too soon
+        assert!(!history.record(&make_battery("BAT0", 95), 3600));
+        // Another battery is recorded separately
+        assert!(history.record(&make_battery("BAT1", 90), 3600));
+        assert!(history.record(&make_battery("BAT0", 94), RECORD_INTERVAL_SECS));
+        // A condition that could not be read is not recorded
+        assert!(!history.record(&make_battery("BAT2", 0), 0));
+
+        assert_eq!(history.battery_names(), vec!["BAT0", "BAT1"]);
+        assert_eq!(history.for_battery("BAT0").len(), 2);
+        assert_eq!(history.for_battery("BAT0")[1].full_wh, 47.0);
+
+        // The history survives being saved and loaded again
+        let path = std::env::temp_dir().join("acs_record_history_unit_test.jsonl");
+        history.save_to(&path)?;
+        let loaded = BatteryHistory::load_from(&path)?;
+        assert_eq!(loaded.entries, history.entries);
+        assert!(!path.with_extension("jsonl.tmp").exists());
+        fs::remove_file(&path)?;
+
+        // A missing file is an empty history
+        assert!(BatteryHistory::load_from(&path)?.entries.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn wear_rate_unit_test() {
+        let month = SECS_PER_MONTH as u64;
+        let entries: Vec<HistoryEntry> = [(0, 100), (month, 99), (2 * month, 98)]
+            .iter()
+            .map(|(timestamp, condition)| HistoryEntry {
+                timestamp: *timestamp,
+                battery: "BAT0".to_string(),
+                cycle_count: 0,
+                full_wh: 0.0,
+                design_wh: 0.0,
+                condition: *condition,
+            })
+            .collect();
+        let refs: Vec<&HistoryEntry> = entries.iter().collect();
+
+        let rate = wear_rate_per_month(&refs).unwrap();
+        assert!((rate - 1.0).abs() < 0.01);
+
+        // One entry is not enough to tell
+        assert_eq!(wear_rate_per_month(&refs[..1]), None);
+    }
+}
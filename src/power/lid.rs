@@ -3,9 +3,15 @@ use crate::error::Error;
 use std::cmp::PartialEq;
 use std::fmt;
 use std::fs;
-use std::path::Path;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
 
-#[derive(PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum LidState {
     Open,
     Closed,
@@ -13,6 +19,18 @@ pub enum LidState {
     Unknown,
 }
 
+const INPUT_DEVICES_PATH: &str = "/proc/bus/input/devices";
+const LID_SWITCH_NAME: &str = "Lid Switch";
+
+/// struct input_event is a timeval followed by type (u16), code (u16) and value (i32)
+const INPUT_EVENT_SIZE: usize = if cfg!(target_pointer_width = "64") {
+    24
+} else {
+    16
+};
+const EV_SW: u16 = 0x05;
+const SW_LID: u16 = 0x00;
+
 fn set_best_path() -> Option<&'static str> {
     static LID_STATUS_PATH: [&str; 4] = [
         "/proc/acpi/button/lid/LID/state",
@@ -21,20 +39,107 @@ fn set_best_path() -> Option<&'static str> {
         "/proc/acpi/button/lid/LID2/state",
     ];
 
-    // Find if any AC power path exists
-    for path in LID_STATUS_PATH.iter() {
-        if Path::new(path).exists() {
-            // Mutate Power struct and leave
-            return Some(path);
-        }
+    // Find if any lid path exists
+    LID_STATUS_PATH
+        .iter()
+        .find(|path| Path::new(path).exists())
+        .copied()
+}
+
+/// Find the event device of the lid switch from the contents of /proc/bus/input/devices
+///
+/// Each device is a block of lines, the lid switch looks like this
+/// N: Name="Lid Switch"
+/// H: Handlers=event0
+fn find_lid_switch(devices: &str) -> Option<String> {
+    devices
+        .split("\n\n")
+        .find(|block| {
+            block
+                .lines()
+                .any(|line| line == format!("N: Name=\"{}\"", LID_SWITCH_NAME))
+        })?
+        .lines()
+        .find_map(|line| line.strip_prefix("H: Handlers="))?
+        .split_whitespace()
+        .find(|handler| handler.starts_with("event"))
+        .map(|handler| handler.to_string())
+}
+
+/// Turn a raw input_event into a lid state, None for any other event
+fn parse_lid_event(event: &[u8]) -> Option<LidState> {
+    let offset = INPUT_EVENT_SIZE - 8;
+    let event_type = u16::from_ne_bytes([event[offset], event[offset + 1]]);
+    let code = u16::from_ne_bytes([event[offset + 2], event[offset + 3]]);
+    let value = i32::from_ne_bytes([
+        event[offset + 4],
+        event[offset + 5],
+        event[offset + 6],
+        event[offset + 7],
+    ]);
+
+    if event_type != EV_SW || code != SW_LID {
+        return None;
+    }
+
+    Some(if value == 1 {
+        LidState::Closed
+    } else {
+        LidState::Open
+    })
+}
+
+/// Read the lid state from logind, which is available without root
+fn read_logind_lid_state() -> Result<LidState, Error> {
+    let output = Command::new("busctl")
+        .args([
+            "get-property",
+            "org.freedesktop.login1",
+            "/org/freedesktop/login1",
+            "org.freedesktop.login1.Manager",
+            "LidClosed",
+        ])
+        .output()?;
+
+    if !output.status.success() {
+        return Err(Error::HdwNotFound);
     }
 
-    None
+    // The output looks like 'b true'
+    Ok(match String::from_utf8_lossy(&output.stdout).trim() {
+        "b true" => LidState::Closed,
+        "b false" => LidState::Open,
+        _ => LidState::Unknown,
+    })
+}
+
+fn read_acpi_lid_state(path: &Path) -> Result<LidState, Error> {
+    let lid_str = fs::read_to_string(path)?;
+
+    Ok(match lid_str.split_whitespace().last() {
+        Some("open") => LidState::Open,
+        Some("closed") => LidState::Closed,
+        _ => LidState::Unknown,
+    })
+}
+
+/// Where the lid state comes from, in order of preference
+enum LidSource {
+    /// Lid switch events, kept up to date by a reader thread
+    ///
+    /// The state is None once the reader has stopped, then the fallback is read instead
+    Evdev(Arc<Mutex<Option<LidState>>>, Box<LidSource>),
+    /// The lid switch exists but cannot be opened without root, so ask logind
+    Logind,
+    /// The legacy ACPI button interface
+    Acpi(PathBuf),
+    None,
 }
 
 pub struct Lid {
-    pub best_path: &'static str,
-    found_path: bool,
+    source: LidSource,
+    /// Receives a message every time the lid is opened or closed
+    wake: Option<Receiver<()>>,
 }
 
 pub trait LidRetriever {
@@ -42,33 +147,92 @@ pub trait LidRetriever {
     fn read_lid_state(&self) -> Result<LidState, Error>;
 }
 
+impl Lid {
+    /// Take the receiver that wakes the daemon up when the lid is opened or closed
+    ///
+    /// Only an evdev lid switch can wake the daemon, other sources are polled
+    pub fn take_wake_receiver(&mut self) -> Option<Receiver<()>> {
+        self.wake.take()
+    }
+
+    /// Start a thread that reads lid switch events and updates the state
+    fn spawn_reader(
+        mut device: File,
+        initial: LidState,
+    ) -> (Arc<Mutex<Option<LidState>>>, Receiver<()>) {
+        let state = Arc::new(Mutex::new(Some(initial)));
+        let (sender, receiver): (Sender<()>, Receiver<()>) = channel();
+
+        let thread_state = Arc::clone(&state);
+        thread::spawn(move || {
+            let mut event = [0u8; INPUT_EVENT_SIZE];
+            while device.read_exact(&mut event).is_ok() {
+                if let Some(lid) = parse_lid_event(&event) {
+                    let mut current = thread_state.lock().unwrap();
+                    if *current != Some(lid) {
+                        *current = Some(lid);
+                        // The daemon may have stopped listening, that is fine
+                        sender.send(()).ok();
+                    }
+                }
+            }
+
+            // The device went away, stop reporting a state that can no longer change
+            *thread_state.lock().unwrap() = None;
+        });
+
+        (state, receiver)
+    }
+}
+
 impl LidRetriever for Lid {
     fn new() -> Self {
-        if let Some(lid) = set_best_path() {
-            Lid {
-                best_path: lid,
-                found_path: true,
-            }
+        let acpi_path = set_best_path();
+        let lid_switch = fs::read_to_string(INPUT_DEVICES_PATH)
+            .ok()
+            .and_then(|devices| find_lid_switch(&devices));
+
+        let fallback = if lid_switch.is_some() && read_logind_lid_state().is_ok() {
+            LidSource::Logind
+        } else if let Some(path) = acpi_path {
+            LidSource::Acpi(PathBuf::from(path))
         } else {
-            Lid {
-                best_path: "",
-                found_path: false,
+            LidSource::None
+        };
+
+        if let Some(event) = &lid_switch {
+            if let Ok(device) = File::open(Path::new("/dev/input").join(event)) {
+                // Events only report changes, so the starting state comes from logind or ACPI
+                let initial = read_source(&fallback).unwrap_or(LidState::Unknown);
+
+                let (state, wake) = Lid::spawn_reader(device, initial);
+                return Lid {
+                    source: LidSource::Evdev(state, Box::new(fallback)),
+                    wake: Some(wake),
+                };
             }
         }
-    }
 
-    fn read_lid_state(&self) -> Result<LidState, Error> {
-        if !self.found_path {
-            return Ok(LidState::Unapplicable);
+        Lid {
+            source: fallback,
+            wake: None,
         }
+    }
 
-        let lid_str = fs::read_to_string(self.best_path)?;
+    fn read_lid_state(&self) -> Result<LidState, Error> {
+        read_source(&self.source)
+    }
+}
 
-        Ok(match lid_str.split_whitespace().last().unwrap() {
-            "open" => LidState::Open,
-            "closed" => LidState::Closed,
-            _ => LidState::Unknown,
-        })
+fn read_source(source: &LidSource) -> Result<LidState, Error> {
+    match source {
+        LidSource::Evdev(state, fallback) => match *state.lock().unwrap() {
+            Some(lid) => Ok(lid),
+            None => read_source(fallback),
+        },
+        LidSource::Logind => read_logind_lid_state(),
+        LidSource::Acpi(path) => read_acpi_lid_state(path),
+        LidSource::None => Ok(LidState::Unapplicable),
     }
 }
 
@@ -82,3 +246,71 @@ impl fmt::Display for LidState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_event(event_type: u16, code: u16, value: i32) -> [u8; INPUT_EVENT_SIZE] {
+        let mut event = [0u8; INPUT_EVENT_SIZE];
+        let offset = INPUT_EVENT_SIZE - 8;
+        event[offset..offset + 2].copy_from_slice(&event_type.to_ne_bytes());
+        event[offset + 2..offset + 4].copy_from_slice(&code.to_ne_bytes());
+        event[offset + 4..].copy_from_slice(&value.to_ne_bytes());
+        event
+    }
+
+    #[test]
+    fn parse_lid_event_unit_test() {
+        assert_eq!(
+            parse_lid_event(&make_event(EV_SW, SW_LID, 1)),
+            Some(LidState::Closed)
+        );
+        assert_eq!(
+            parse_lid_event(&make_event(EV_SW, SW_LID, 0)),
+            Some(LidState::Open)
+        );
+
+        // EV_SYN and other switches such as SW_TABLET_MODE are ignored
+        assert_eq!(parse_lid_event(&make_event(0x00, 0x00, 0)), None);
+        assert_eq!(parse_lid_event(&make_event(EV_SW, 0x01, 1)), None);
+    }
+
+    #[test]
+    fn find_lid_switch_unit_test() {
+        let devices = "I: Bus=0019 Vendor=0000 Product=0005 Version=0000
+N: Name=\"Power Button\"
+P: Phys=PNP0C0C/button/input0
+H: Handlers=kbd event1
+B: EV=3
+
+I: Bus=0019 Vendor=0000 Product=0005 Version=0000
+N: Name=\"Lid Switch\"
+P: Phys=PNP0C0D/button/input0
+H: Handlers=event0
+B: EV=21
+B: SW=1
+";
+        assert_eq!(find_lid_switch(devices), Some("event0".to_string()));
+        assert_eq!(find_lid_switch(&devices[..100]), None);
+    }
+
+    #[test]
+    fn reader_fallback_unit_test() {
+        // An empty file ends straight away, like a lid switch that was removed
+        let path = std::env::temp_dir().join("acs_reader_fallback_unit_test");
+        fs::write(&path, "").unwrap();
+
+        let (state, wake) = Lid::spawn_reader(File::open(&path).unwrap(), LidState::Open);
+        let lid = Lid {
+            source: LidSource::Evdev(state, Box::new(LidSource::None)),
+            wake: None,
+        };
+
+        // The sender is dropped when the reader stops
+        assert!(wake.recv().is_err());
+        assert_eq!(lid.read_lid_state().unwrap(), LidState::Unapplicable);
+
+        fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,290 @@
+#![forbid(unsafe_code)]
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::power::{list_power_supplies_in, PowerSupplyType};
+use crate::sysfs;
+
+const SYSFS_POWER_SUPPLY_PATH: &str = "/sys/class/power_supply/";
+const NUT_TIMEOUT: Duration = Duration::from_millis(500);
+
+fn default_nut_address() -> String {
+    String::from("127.0.0.1:3493")
+}
+
+/// The [ups] section of the config, used to reach a NUT upsd server
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct UpsConfig {
+    /// The address of upsd, 127.0.0.1:3493 by default
+    #[serde(default = "default_nut_address")]
+    pub address: String,
+    /// The name of the UPS in upsd, the first UPS listed when not set
+    pub name: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum UpsSource {
+    /// A power_supply entry of type UPS, e.g. from a USB HID UPS
+    Sysfs(PathBuf),
+    /// A UPS managed by Network UPS Tools
+    Nut { address: String, name: String },
+}
+
+/// An uninterruptible power supply the computer is plugged into
+#[derive(Clone, Debug)]
+pub struct Ups {
+    source: UpsSource,
+    pub name: String,
+    /// If the UPS is running from its own battery because mains power is gone
+    pub on_battery: bool,
+    /// The charge of the UPS battery in percent
+    pub charge: i8,
+}
+
+impl Ups {
+    /// Find a UPS, power_supply entries are checked before upsd
+    pub fn find(config: Option<&UpsConfig>) -> Option<Ups> {
+        Ups::find_in_sysfs(Path::new(SYSFS_POWER_SUPPLY_PATH)).or_else(|| Ups::find_in_nut(config?))
+    }
+
+    /// Find a UPS through upsd
+    pub fn find_in_nut(config: &UpsConfig) -> Option<Ups> {
+        let name = match &config.name {
+            Some(name) => name.clone(),
+            None => nut_list_ups(&config.address).ok()?.into_iter().next()?,
+        };
+
+        let mut ups = Ups {
+            source: UpsSource::Nut {
+                address: config.address.clone(),
+                name: name.clone(),
+            },
+            name,
+            on_battery: false,
+            charge: 100,
+        };
+        ups.update().ok()?;
+        Some(ups)
+    }
+
+    /// Find the first power_supply of type UPS within a directory
+    pub fn find_in_sysfs(parent_path: &Path) -> Option<Ups> {
+        let supply = list_power_supplies_in(parent_path)
+            .ok()?
+            .into_iter()
+            .find(|s| s.supply_type == PowerSupplyType::UPS)?;
+
+        let mut ups = Ups {
+            source: UpsSource::Sysfs(supply.path),
+            name: supply.name,
+            on_battery: false,
+            charge: 100,
+        };
+        ups.update().ok()?;
+        Some(ups)
+    }
+
+    /// Read the current status of the UPS
+    pub fn update(&mut self) -> Result<(), Error> {
+        match &self.source {
+            UpsSource::Sysfs(path) => {
+                let mut capacity: i8 = 100;
+                sysfs::read(&mut capacity, &path.join("capacity"))?;
+
+                let mut status = String::new();
+                sysfs::read(&mut status, &path.join("status")).ok();
+
+                let mut online: u8 = 1;
+                sysfs::read(&mut online, &path.join("online")).ok();
+
+                self.charge = capacity;
+                self.on_battery = status == "Discharging" || online == 0;
+            }
+            UpsSource::Nut { address, name } => {
+                let mut stream = nut_connect(address)?;
+                let status = nut_get_var(&mut stream, name, "ups.status")?;
+                let charge = nut_get_var(&mut stream, name, "battery.charge")?;
+
+                self.charge = charge
+                    .parse::<f32>()
+                    .map_err(|_| Error::Parse)?
+                    .clamp(0.0, 100.0) as i8;
+                // OL is online, OB is on battery, LB is low battery
+                self.on_battery = status.split_whitespace().any(|flag| flag == "OB");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn nut_connect(address: &str) -> Result<BufReader<TcpStream>, Error> {
+    let address = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| Error::InvalidValue(address.to_string()))?;
+
+    let stream = TcpStream::connect_timeout(&address, NUT_TIMEOUT)?;
+    stream.set_read_timeout(Some(NUT_TIMEOUT))?;
+    stream.set_write_timeout(Some(NUT_TIMEOUT))?;
+    Ok(BufReader::new(stream))
+}
+
+fn nut_read_line(stream: &mut BufReader<TcpStream>) -> Result<String, Error> {
+    let mut line = String::new();
+    if stream.read_line(&mut line)? == 0 {
+        return Err(Error::Parse);
+    }
+
+    let line = line.trim_end().to_string();
+    if line.starts_with("ERR ") {
+        return Err(Error::InvalidValue(line));
+    }
+    Ok(line)
+}
+
+/// Ask upsd for a variable, e.g. 'GET VAR myups ups.status' gives 'VAR myups ups.status "OL"'
+fn nut_get_var(stream: &mut BufReader<TcpStream>, name: &str, var: &str) -> Result<String, Error> {
+    stream
+        .get_mut()
+        .write_all(format!("GET VAR {} {}\n", name, var).as_bytes())?;
+
+    let line = nut_read_line(stream)?;
+    let prefix = format!("VAR {} {} ", name, var);
+    line.strip_prefix(&prefix)
+        .map(|value| value.trim_matches('"').to_string())
+        .ok_or(Error::Parse)
+}
+
+/// List the names of every UPS known to upsd
+fn nut_list_ups(address: &str) -> Result<Vec<String>, Error> {
+    let mut stream = nut_connect(address)?;
+    stream.get_mut().write_all(b"LIST UPS\n")?;
+
+    if nut_read_line(&mut stream)? != "BEGIN LIST UPS" {
+        return Err(Error::Parse);
+    }
+
+    let mut names = Vec::<String>::new();
+    loop {
+        let line = nut_read_line(&mut stream)?;
+        if line == "END LIST UPS" {
+            break;
+        }
+        // UPS <name> "<description>"
+        if let Some(name) = line.split_whitespace().nth(1) {
+            names.push(name.to_string());
+        }
+    }
+
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// A stand-in for upsd that answers every connection with the given status and charge
+    fn fake_upsd(status: &'static str, charge: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut stream = stream;
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap_or(0) > 0 {
+                    let response = match line.trim_end() {
+                        "LIST UPS" => {
+                            "BEGIN LIST UPS\nUPS lab \"Lab UPS\"\nEND LIST UPS\n".to_string()
+                        }
+                        "GET VAR lab ups.status" => format!("VAR lab ups.status \"{}\"\n", status),
+                        "GET VAR lab battery.charge" => {
+                            format!("VAR lab battery.charge \"{}\"\n", charge)
+                        }
+                        _ => "ERR UNKNOWN-UPS\n".to_string(),
+                    };
+                    stream.write_all(response.as_bytes()).unwrap();
+                    line.clear();
+                }
+            }
+        });
+
+        address
+    }
+
+    #[test]
+    fn nut_ups_unit_test() -> Result<(), Error> {
+        let address = fake_upsd("OB LB", "42.0");
+
+        assert_eq!(nut_list_ups(&address)?, vec!["lab".to_string()]);
+
+        let ups = Ups::find_in_nut(&UpsConfig {
+            address: address.clone(),
+            name: None,
+        })
+        .ok_or(Error::HdwNotFound)?;
+
+        assert_eq!(ups.name, "lab");
+        assert!(ups.on_battery);
+        assert_eq!(ups.charge, 42);
+
+        let mut stream = nut_connect(&address)?;
+        assert!(matches!(
+            nut_get_var(&mut stream, "missing", "ups.status"),
+            Err(Error::InvalidValue(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn nut_ups_online_unit_test() -> Result<(), Error> {
+        let address = fake_upsd("OL CHRG", "97");
+
+        let mut ups = Ups {
+            source: UpsSource::Nut {
+                address,
+                name: "lab".to_string(),
+            },
+            name: "lab".to_string(),
+            on_battery: true,
+            charge: 0,
+        };
+        ups.update()?;
+
+        assert!(!ups.on_battery);
+        assert_eq!(ups.charge, 97);
+        Ok(())
+    }
+
+    #[test]
+    fn sysfs_ups_unit_test() -> Result<(), Error> {
+        let parent = std::env::temp_dir().join("acs_sysfs_ups_unit_test");
+        fs::remove_dir_all(&parent).ok();
+
+        let path = parent.join("hid-0003:0764:0501.0001-battery");
+        fs::create_dir_all(&path)?;
+        fs::write(path.join("type"), "UPS\n")?;
+        fs::write(path.join("capacity"), "80\n")?;
+        fs::write(path.join("status"), "Discharging\n")?;
+
+        let ups = Ups::find_in_sysfs(&parent).ok_or(Error::HdwNotFound)?;
+        assert!(ups.on_battery);
+        assert_eq!(ups.charge, 80);
+
+        fs::write(path.join("status"), "Charging\n")?;
+        let ups = Ups::find_in_sysfs(&parent).ok_or(Error::HdwNotFound)?;
+        assert!(!ups.on_battery);
+
+        fs::remove_dir_all(&parent)?;
+        Ok(())
+    }
+}
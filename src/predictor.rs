@@ -0,0 +1,355 @@
+#![forbid(unsafe_code)]
+//! Predicts cpu usage bursts from the daemon's own history
+//!
+//! The cpu usage rule waits high_cpu_time_needed before it applies, so it is late to every burst.
+//! The predictor counts how often the usage was high at each time of day and while each process
+//! was running, and when a burst is likely the cpu usage rule applies straight away. Everything is
+//! counted locally and saved to /var/lib/acs/predictor.json, the same counts always give the same
+//! prediction.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use crate::error::Error;
+
+pub const PREDICTOR_FILE: &str = "/var/lib/acs/predictor.json";
+
+/// The day is split into slots of this many minutes
+const SLOT_MINUTES: u32 = 15;
+const SLOTS: usize = (24 * 60 / SLOT_MINUTES) as usize;
+
+/// Counts are halved when they reach this, so that old habits fade out
+const MAX_SAMPLES: u32 = 10_000;
+
+/// The most processes that are counted, the ones least tied to bursts are forgotten first
+const MAX_PROCESSES: usize = 256;
+
+fn default_probability() -> f32 {
+    0.75
+}
+
+fn default_min_samples() -> u32 {
+    60
+}
+
+/// Turns the predictor on, set with a [predictor] table in the config
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub struct PredictorConfig {
+    /// How likely a burst must be for the cpu usage rule to apply before it happens
+    #[serde(default = "default_probability")]
+    pub probability: f32,
+    /// How many updates a time of day or process needs to be seen for before it is trusted
+    #[serde(default = "default_min_samples")]
+    pub min_samples: u32,
+}
+
+impl Default for PredictorConfig {
+    fn default() -> PredictorConfig {
+        PredictorConfig {
+            probability: default_probability(),
+            min_samples: default_min_samples(),
+        }
+    }
+}
+
+/// How many updates something was seen in, and in how many of them the usage was high
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Counts {
+    pub seen: u32,
+    pub busy: u32,
+}
+
+impl Counts {
+    fn add(&mut self, busy: bool) {
+        if self.seen >= MAX_SAMPLES {
+            self.seen /= 2;
+            self.busy /= 2;
+        }
+        self.seen += 1;
+        if busy {
+            self.busy += 1;
+        }
+    }
+
+    /// The share of updates that were busy, None until there are enough of them
+    fn probability(&self, min_samples: u32) -> Option<f32> {
+        (self.seen >= min_samples.max(1)).then(|| self.busy as f32 / self.seen as f32)
+    }
+}
+
+/// A likely burst and what it was predicted from
+#[derive(Clone, Debug, PartialEq)]
+pub struct Prediction {
+    pub probability: f32,
+    /// e.g. process cargo or time of day 09:00
+    pub source: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Predictor {
+    /// One for each slot of the day
+    pub slots: Vec<Counts>,
+    /// Processes that have been running during a burst
+    pub processes: BTreeMap<String, Counts>,
+    /// How many predictions were checked, and how many of them were right
+    pub predictions: u64,
+    pub correct: u64,
+}
+
+impl Default for Predictor {
+    fn default() -> Predictor {
+        Predictor {
+            slots: vec![Counts::default(); SLOTS],
+            processes: BTreeMap::new(),
+            predictions: 0,
+            correct: 0,
+        }
+    }
+}
+
+fn slot(minute_of_day: u32) -> usize {
+    (minute_of_day / SLOT_MINUTES) as usize % SLOTS
+}
+
+impl Predictor {
+    /// The most likely reason for a burst right now, None when nothing has been seen enough
+    pub fn predict(
+        &self,
+        minute_of_day: u32,
+        processes: &[String],
+        min_samples: u32,
+    ) -> Option<Prediction> {
+        let slot = slot(minute_of_day);
+        let by_time = self.slots.get(slot)?.probability(min_samples).map(|p| {
+            let start = slot as u32 * SLOT_MINUTES;
+            Prediction {
+                probability: p,
+                source: format!("time of day {:02}:{:02}", start / 60, start % 60),
+            }
+        });
+
+        // The names are sorted so that ties always go to the same process
+        let names: BTreeSet<&String> = processes.iter().collect();
+        let by_process = names.into_iter().filter_map(|name| {
+            let probability = self.processes.get(name)?.probability(min_samples)?;
+            Some(Prediction {
+                probability,
+                source: format!("process {}", name),
+            })
+        });
+
+        by_time
+            .into_iter()
+            .chain(by_process)
+            .fold(None, |best, p| match best {
+                Some(best) if best.probability >= p.probability => Some(best),
+                _ => Some(p),
+            })
+    }
+
+    /// Learn from one update, processes only start being counted once they run during a burst
+    pub fn observe(&mut self, minute_of_day: u32, processes: &[String], busy: bool) {
+        self.slots[slot(minute_of_day)].add(busy);
+
+        let names: BTreeSet<&String> = processes.iter().collect();
+        for name in &names {
+            if busy {
+                self.processes
+                    .entry(name.to_string())
+                    .or_default()
+                    .add(true);
+            } else if let Some(counts) = self.processes.get_mut(*name) {
+                counts.add(false);
+            }
+        }
+
+        // Processes that are not running go first, so that new ones can still be learned
+        while self.processes.len() > MAX_PROCESSES {
+            let weakest = self
+                .processes
+                .iter()
+                .min_by_key(|(name, counts)| (names.contains(name), counts.busy, counts.seen))
+                .map(|(name, _)| name.clone());
+            match weakest {
+                Some(name) => self.processes.remove(&name),
+                None => break,
+            };
+        }
+    }
+
+    /// Check a prediction against what happened
+    pub fn score(&mut self, predicted_busy: bool, busy: bool) {
+        self.predictions += 1;
+        if predicted_busy == busy {
+            self.correct += 1;
+        }
+    }
+
+    /// The share of predictions that were right, None before any were made
+    pub fn accuracy(&self) -> Option<f32> {
+        (self.predictions > 0).then(|| self.correct as f32 / self.predictions as f32)
+    }
+
+    /// Read the counts from a file, a missing file is a predictor that has not learned anything
+    pub fn load_from(path: &Path) -> Result<Predictor, Error> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Predictor::default()),
+            Err(e) => return Err(Error::IO(e)),
+        };
+
+        let predictor: Predictor = serde_json::from_str(&contents).map_err(|_| Error::Parse)?;
+        if predictor.slots.len() != SLOTS {
+            return Err(Error::Parse);
+        }
+        Ok(predictor)
+    }
+
+    pub fn load() -> Result<Predictor, Error> {
+        Predictor::load_from(Path::new(PREDICTOR_FILE))
+    }
+
+    /// Write the counts to a file, through a temporary file so that a crash can not leave half
+    /// of them behind
+    pub fn save_to(&self, path: &Path) -> Result<(), Error> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string(self).map_err(|_| Error::Parse)?;
+        let temp = path.with_extension("json.tmp");
+        fs::write(&temp, json)?;
+        fs::rename(&temp, path)?;
+        Ok(())
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        self.save_to(Path::new(PREDICTOR_FILE))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn predict_by_time_unit_test() {
+        let mut predictor = Predictor::default();
+        let nine = 9 * 60;
+
+        // Busy every morning at nine, quiet at noon
+        for i in 0..10 {
+            predictor.observe(nine + i, &[], true);
+            predictor.observe(12 * 60 + i, &[], false);
+        }
+
+        assert_eq!(predictor.predict(nine + 5, &[], 20), None);
+        assert_eq!(
+            predictor.predict(nine + 14, &[], 10),
+            Some(Prediction {
+                probability: 1.0,
+                source: "time of day 09:00".to_string(),
+            })
+        );
+        assert_eq!(
+            predictor.predict(12 * 60, &[], 10).map(|p| p.probability),
+            Some(0.0)
+        );
+        // The next slot has not been seen
+        assert_eq!(predictor.predict(nine + 15, &[], 10), None);
+    }
+
+    #[test]
+    fn predict_by_process_unit_test() {
+        let mut predictor = Predictor::default();
+        let minute = 20 * 60;
+
+        // cargo is always busy, firefox only half of the time
+        for i in 0..20 {
+            let mut running = names(&["firefox"]);
+            if i % 2 == 0 {
+                running.push("cargo".to_string());
+            }
+            predictor.observe(minute, &running, i % 2 == 0);
+        }
+        // Processes that never ran during a burst are not counted
+        predictor.observe(minute, &names(&["sshd"]), false);
+        assert!(!predictor.processes.contains_key("sshd"));
+
+        let prediction = predictor.predict(minute, &names(&["firefox", "cargo"]), 10);
+        assert_eq!(
+            prediction,
+            Some(Prediction {
+                probability: 1.0,
+                source: "process cargo".to_string(),
+            })
+        );
+        let prediction = predictor.predict(minute, &names(&["firefox"]), 10);
+        assert_eq!(prediction.map(|p| p.probability), Some(0.5));
+    }
+
+    #[test]
+    fn max_processes_unit_test() {
+        let mut predictor = Predictor::default();
+        let running: Vec<String> = (0..MAX_PROCESSES).map(|i| format!("old{}", i)).collect();
+        predictor.observe(0, &running, true);
+        predictor.observe(0, &running[..1], true);
+
+        // The new process takes the place of one that is not running and was busy the least
+        predictor.observe(0, &names(&["old0", "cargo"]), true);
+        assert_eq!(predictor.processes.len(), MAX_PROCESSES);
+        assert!(predictor.processes.contains_key("cargo"));
+        assert!(predictor.processes.contains_key("old0"));
+        assert!(!predictor.processes.contains_key("old1"));
+    }
+
+    #[test]
+    fn counts_unit_test() {
+        let mut counts = Counts {
+            seen: MAX_SAMPLES,
+            busy: 3000,
+        };
+        counts.add(true);
+        assert_eq!(
+            counts,
+            Counts {
+                seen: MAX_SAMPLES / 2 + 1,
+                busy: 1501,
+            }
+        );
+        assert_eq!(Counts::default().probability(0), None);
+    }
+
+    #[test]
+    fn accuracy_unit_test() {
+        let mut predictor = Predictor::default();
+        assert_eq!(predictor.accuracy(), None);
+
+        predictor.score(true, true);
+        predictor.score(false, false);
+        predictor.score(true, false);
+        predictor.score(false, false);
+        assert_eq!(predictor.accuracy(), Some(0.75));
+    }
+
+    #[test]
+    fn save_predictor_unit_test() -> Result<(), Error> {
+        let path = std::env::temp_dir().join("acs_save_predictor_unit_test.json");
+        let mut predictor = Predictor::default();
+        predictor.observe(60, &names(&["ffmpeg"]), true);
+        predictor.score(false, true);
+
+        predictor.save_to(&path)?;
+        assert_eq!(Predictor::load_from(&path)?, predictor);
+        assert!(!path.with_extension("json.tmp").exists());
+
+        fs::remove_file(&path)?;
+        assert_eq!(Predictor::load_from(&path)?, Predictor::default());
+        Ok(())
+    }
+}
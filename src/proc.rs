@@ -1,5 +1,5 @@
 #![forbid(unsafe_code)]
-use std::fs::read_to_string;
+use std::fs::{read_dir, read_to_string};
 
 use crate::error::Error;
 
@@ -28,6 +28,64 @@ pub fn read_proc_stat_file() -> Result<String, Error> {
     Ok(proc_stat_content)
 }
 
+/// A running process read from /proc/<pid>
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    /// The executable name, as in /proc/<pid>/comm
+    pub name: String,
+    /// The arguments separated by spaces, empty unless it was asked for
+    pub cmdline: String,
+    /// The contents of /proc/<pid>/cgroup, empty unless it was asked for
+    pub cgroup: String,
+}
+
+/// Every running process, the cmdline and cgroup are only read when asked for
+pub fn list_processes(with_cmdline: bool, with_cgroup: bool) -> Vec<ProcessInfo> {
+    let mut processes = Vec::<ProcessInfo>::new();
+
+    for entry in read_dir("/proc").into_iter().flatten().flatten() {
+        let pid = match entry.file_name().to_string_lossy().parse::<u32>() {
+            Ok(pid) => pid,
+            Err(_) => continue,
+        };
+
+        // The process may have exited since the directory was read
+        let name = match read_to_string(entry.path().join("comm")) {
+            Ok(comm) => comm.trim_end().to_string(),
+            Err(_) => continue,
+        };
+
+        let cmdline = if with_cmdline {
+            // Arguments are separated by null characters
+            read_to_string(entry.path().join("cmdline"))
+                .unwrap_or_default()
+                .trim_end_matches('\0')
+                .replace('\0', " ")
+        } else {
+            String::new()
+        };
+
+        let cgroup = if with_cgroup {
+            read_to_string(entry.path().join("cgroup"))
+                .unwrap_or_default()
+                .trim_end()
+                .to_string()
+        } else {
+            String::new()
+        };
+
+        processes.push(ProcessInfo {
+            pid,
+            name,
+            cmdline,
+            cgroup,
+        });
+    }
+
+    processes
+}
+
 /// Parse the `/proc/stat` into a list of `ProcStat` structs for each CPU core
 pub fn parse_proc_file(proc: String) -> Vec<ProcStat> {
     let lines: Vec<_> = proc.lines().collect();
@@ -0,0 +1,162 @@
+#![forbid(unsafe_code)]
+//! Named power profiles that can be switched while the daemon runs
+//!
+//! Each profile in the profiles table of the config changes rule thresholds and what the states
+//! set, e.g. a quiet profile that goes to powersave sooner. `acs profile set quiet` switches the
+//! running daemon over /tmp/acs.sock, and the choice is kept in /var/lib/acs/profile so that it
+//! lasts through restarts.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::config::Config;
+use crate::error::Error;
+use crate::rules::Action;
+
+pub const PROFILE_FILE: &str = "/var/lib/acs/profile";
+
+/// What a profile changes, anything it does not set is left as in the config
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Profile {
+    pub powersave_under: Option<i8>,
+    pub overheat_threshold: Option<i8>,
+    pub high_cpu_threshold: Option<i8>,
+    pub high_cpu_time_needed: Option<u64>,
+    pub idle_minutes: Option<u64>,
+    /// What each state sets, on top of the states table of the config
+    #[serde(default)]
+    pub states: BTreeMap<String, Action>,
+}
+
+impl Profile {
+    /// The config with this profile applied
+    pub fn apply(&self, config: &Config) -> Config {
+        let mut config = config.clone();
+
+        if let Some(pu) = self.powersave_under {
+            config.powersave_under = pu;
+        }
+
+        if let Some(ot) = self.overheat_threshold {
+            config.overheat_threshold = ot;
+        }
+
+        if let Some(hc) = self.high_cpu_threshold {
+            config.high_cpu_threshold = hc;
+        }
+
+        if let Some(ht) = self.high_cpu_time_needed {
+            config.high_cpu_time_needed = ht;
+        }
+
+        if let Some(im) = self.idle_minutes {
+            config.idle_minutes = im;
+        }
+
+        for (state, action) in &self.states {
+            let base = config.states.get(state).cloned().unwrap_or_default();
+            config.states.insert(state.clone(), action.clone().or(base));
+        }
+
+        config
+    }
+}
+
+/// Read the active profile from a file, a missing file means no profile
+pub fn load_active_profile_from(path: &Path) -> Result<Option<String>, Error> {
+    match fs::read_to_string(path) {
+        Ok(contents) => {
+            let name = contents.trim();
+            Ok((!name.is_empty()).then(|| name.to_string()))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(Error::IO(e)),
+    }
+}
+
+pub fn load_active_profile() -> Result<Option<String>, Error> {
+    load_active_profile_from(Path::new(PROFILE_FILE))
+}
+
+/// Keep the active profile for the next time the daemon starts, None removes it
+pub fn save_active_profile_to(path: &Path, profile: Option<&str>) -> Result<(), Error> {
+    match profile {
+        Some(name) => {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, format!("{}\n", name))?;
+        }
+        None => match fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(Error::IO(e)),
+            _ => {}
+        },
+    }
+
+    Ok(())
+}
+
+pub fn save_active_profile(profile: Option<&str>) -> Result<(), Error> {
+    save_active_profile_to(Path::new(PROFILE_FILE), profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_profile_unit_test() {
+        let mut config = Config::default();
+        config.states.insert(
+            "cpu_usage_rule".to_string(),
+            Action {
+                governor: Some("performance".to_string()),
+                turbo: Some(true),
+                ..Default::default()
+            },
+        );
+
+        let quiet = Profile {
+            powersave_under: Some(50),
+            high_cpu_threshold: Some(90),
+            states: BTreeMap::from([(
+                "cpu_usage_rule".to_string(),
+                Action {
+                    turbo: Some(false),
+                    ..Default::default()
+                },
+            )]),
+            ..Default::default()
+        };
+
+        let applied = quiet.apply(&config);
+        assert_eq!(applied.powersave_under, 50);
+        assert_eq!(applied.high_cpu_threshold, 90);
+        assert_eq!(applied.overheat_threshold, config.overheat_threshold);
+        // The profile only replaces what it sets in a state
+        assert_eq!(
+            applied.states["cpu_usage_rule"],
+            Action {
+                governor: Some("performance".to_string()),
+                turbo: Some(false),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn save_active_profile_unit_test() -> Result<(), Error> {
+        let path = std::env::temp_dir().join("acs_save_active_profile_unit_test");
+        save_active_profile_to(&path, None)?;
+        assert_eq!(load_active_profile_from(&path)?, None);
+
+        save_active_profile_to(&path, Some("quiet"))?;
+        assert_eq!(load_active_profile_from(&path)?, Some("quiet".to_string()));
+
+        save_active_profile_to(&path, None)?;
+        assert_eq!(load_active_profile_from(&path)?, None);
+        Ok(())
+    }
+}
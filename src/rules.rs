@@ -0,0 +1,814 @@
+#![forbid(unsafe_code)]
+//! User defined rules
+//!
+//! Rules in acs.toml pair a set of conditions with the actions to take while they hold. Every
+//! condition of a rule must hold for the rule to apply, and when several rules apply the one with
+//! the highest priority wins. A rule that applies takes precedence over the built in rules, other
+//! than safety critical ones such as overheating.
+//!
+//! ```toml
+//! [[rules]]
+//! name = "busy_on_battery"
+//! priority = 10
+//! [rules.conditions]
+//! ac = false
+//! usage_above = 70
+//! for_secs = 30
+//! [rules.actions]
+//! governor = "schedutil"
+//! turbo = false
+//! ```
+
+use cached::proc_macro::cached;
+use chrono::{NaiveTime, Weekday};
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::time::SystemTime;
+
+use crate::cpu::CPU;
+use crate::error::Error;
+use crate::gov::Gov;
+use crate::platform_profile::PlatformProfile;
+use crate::power::lid::LidState;
+use crate::proc::ProcessInfo;
+use crate::system::{
+    can_set_turbo, check_available_epps, check_available_governors, read_cpuinfo_max_freq,
+    read_cpuinfo_min_freq,
+};
+
+/// Every condition that is set must hold for a rule to apply
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Conditions {
+    /// Battery percent
+    pub battery_below: Option<i8>,
+    pub battery_above: Option<i8>,
+    /// Plugged in to a full power adapter
+    pub ac: Option<bool>,
+    pub lid_closed: Option<bool>,
+    /// Average cpu usage percent
+    pub usage_above: Option<f32>,
+    pub usage_below: Option<f32>,
+    /// Highest cpu temperature in celsius
+    pub temp_above: Option<i8>,
+    pub temp_below: Option<i8>,
+    /// Local time as HH:MM, after can be later than before to wrap around midnight
+    pub after: Option<String>,
+    pub before: Option<String>,
+    /// Days of the week such as mon or saturday, weekdays and weekends stand for several days
+    pub days: Option<Vec<String>>,
+    /// Names of processes as in /proc/<pid>/comm, any one of them has to be running
+    #[serde(default, alias = "process", deserialize_with = "one_or_many")]
+    pub processes: Option<Vec<String>>,
+    /// A regex that the arguments of a running process have to match
+    pub cmdline: Option<String>,
+    /// Part of the cgroup of a running process, e.g. app-zoom
+    pub cgroup: Option<String>,
+    /// How many seconds the other conditions must hold before the rule applies
+    pub for_secs: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// A single process name or a list of them, process = "zoom" is how rules were first written
+fn one_or_many<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<String>>, D::Error> {
+    Ok(
+        Option::<OneOrMany>::deserialize(deserializer)?.map(|names| match names {
+            OneOrMany::One(name) => vec![name],
+            OneOrMany::Many(names) => names,
+        }),
+    )
+}
+
+/// What to set while a rule applies, anything not set is left as in the normal state
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Action {
+    pub governor: Option<String>,
+    /// Energy performance preference, e.g. balance_power
+    pub epp: Option<String>,
+    /// The highest frequency the cpus can run at in kHz
+    pub max_freq: Option<i32>,
+    pub turbo: Option<bool>,
+    pub platform_profile: Option<String>,
+}
+
+/// What the hardware supports, actions are checked against it when acs starts
+#[derive(Clone, Debug, Default)]
+pub struct Capabilities {
+    pub governors: Vec<String>,
+    pub epps: Vec<String>,
+    /// The range max_freq can be set to in kHz, None when it cannot be read
+    pub freq_range: Option<(i32, i32)>,
+    pub turbo: bool,
+    pub platform_profiles: Vec<String>,
+}
+
+impl Capabilities {
+    pub fn read(cpus: &[CPU]) -> Capabilities {
+        let freq_range = cpus.first().and_then(|cpu| {
+            Some((
+                read_cpuinfo_min_freq(cpu).ok()?,
+                read_cpuinfo_max_freq(cpu).ok()?,
+            ))
+        });
+
+        Capabilities {
+            governors: check_available_governors().unwrap_or_default(),
+            epps: check_available_epps().unwrap_or_default(),
+            freq_range,
+            turbo: can_set_turbo(),
+            platform_profiles: PlatformProfile::new().choices().unwrap_or_default(),
+        }
+    }
+}
+
+impl Action {
+    /// Check that the hardware supports everything this action sets
+    pub fn validate(&self, capabilities: &Capabilities) -> Result<(), Error> {
+        if let Some(governor) = &self.governor {
+            governor.parse::<Gov>()?;
+            if !capabilities.governors.contains(governor) {
+                return Err(Error::InvalidValue(format!(
+                    "governor {} is not one of {}",
+                    governor,
+                    capabilities.governors.join(", ")
+                )));
+            }
+        }
+
+        if let Some(epp) = &self.epp {
+            if !capabilities.epps.contains(epp) {
+                return Err(Error::InvalidValue(format!(
+                    "energy performance preference {} is not one of {}",
+                    epp,
+                    capabilities.epps.join(", ")
+                )));
+            }
+        }
+
+        if let Some(max_freq) = self.max_freq {
+            match capabilities.freq_range {
+                Some((min, max)) if (min..=max).contains(&max_freq) => {}
+                Some((min, max)) => {
+                    return Err(Error::InvalidValue(format!(
+                        "max_freq {} is not between {} and {} kHz",
+                        max_freq, min, max
+                    )))
+                }
+                None => {
+                    return Err(Error::InvalidValue(
+                        "the frequency range of the cpu could not be read".to_string(),
+                    ))
+                }
+            }
+        }
+
+        if self.turbo.is_some() && !capabilities.turbo {
+            return Err(Error::InvalidValue(
+                "turbo cannot be turned on or off on this system".to_string(),
+            ));
+        }
+
+        if let Some(profile) = &self.platform_profile {
+            if !capabilities.platform_profiles.contains(profile) {
+                return Err(Error::InvalidValue(format!(
+                    "platform profile {} is not one of {}",
+                    profile,
+                    capabilities.platform_profiles.join(", ")
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fill anything not set in this action from another action
+    pub fn or(self, other: Action) -> Action {
+        Action {
+            governor: self.governor.or(other.governor),
+            epp: self.epp.or(other.epp),
+            max_freq: self.max_freq.or(other.max_freq),
+            turbo: self.turbo.or(other.turbo),
+            platform_profile: self.platform_profile.or(other.platform_profile),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub name: String,
+    /// Higher priorities win, rules with the same priority are taken in the order they are written
+    #[serde(default)]
+    pub priority: i32,
+    #[serde(default)]
+    pub conditions: Conditions,
+    #[serde(default)]
+    pub actions: Action,
+}
+
+/// What the daemon knows about the system, which the conditions are checked against
+#[derive(Clone, Debug)]
+pub struct RuleInputs {
+    pub charge: i8,
+    pub charging: bool,
+    pub lid: LidState,
+    pub usage: f32,
+    pub temp: i8,
+    pub time: NaiveTime,
+    pub weekday: Weekday,
+    /// The running processes, only read when a rule needs them
+    pub processes: Vec<ProcessInfo>,
+}
+
+fn parse_time(time: &str) -> Result<NaiveTime, Error> {
+    NaiveTime::parse_from_str(time, "%H:%M")
+        .map_err(|_| Error::InvalidValue(format!("'{}' is not a time like 22:30", time)))
+}
+
+const WEEKDAYS: [Weekday; 5] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+];
+const WEEKENDS: [Weekday; 2] = [Weekday::Sat, Weekday::Sun];
+
+fn parse_days(names: &[String]) -> Result<Vec<Weekday>, Error> {
+    let mut days = Vec::<Weekday>::new();
+    for name in names {
+        match name.to_lowercase().as_str() {
+            "weekdays" => days.extend(WEEKDAYS),
+            "weekends" => days.extend(WEEKENDS),
+            day => days.push(day.parse::<Weekday>().map_err(|_| {
+                Error::InvalidValue(format!("'{}' is not a day of the week", name))
+            })?),
+        }
+    }
+    Ok(days)
+}
+
+/// Compiled once for each pattern, as the rules are checked on every update
+#[cached]
+fn compile_pattern(pattern: String) -> Result<Regex, String> {
+    Regex::new(&pattern).map_err(|e| e.to_string())
+}
+
+/// If a time is between after and before, wrapping around midnight when after is later
+fn time_between(time: NaiveTime, after: Option<NaiveTime>, before: Option<NaiveTime>) -> bool {
+    match (after, before) {
+        (Some(after), Some(before)) if after > before => time >= after || time < before,
+        (after, before) => after.is_none_or(|a| time >= a) && before.is_none_or(|b| time < b),
+    }
+}
+
+impl Conditions {
+    /// If every condition holds right now, for_secs is handled by the RuleEngine
+    ///
+    /// A condition that can not be read never holds, so it can not make a rule apply more often
+    pub fn matches(&self, inputs: &RuleInputs) -> bool {
+        let (after, before) = match (
+            self.after.as_deref().map(parse_time).transpose(),
+            self.before.as_deref().map(parse_time).transpose(),
+        ) {
+            (Ok(after), Ok(before)) => (after, before),
+            _ => return false,
+        };
+
+        // After midnight, a window that wraps around midnight belongs to the day it started on
+        let wraps = matches!((after, before), (Some(a), Some(b)) if a > b);
+        let day = match before {
+            Some(before) if wraps && inputs.time < before => inputs.weekday.pred(),
+            _ => inputs.weekday,
+        };
+
+        self.battery_below.is_none_or(|b| inputs.charge < b)
+            && self.battery_above.is_none_or(|b| inputs.charge > b)
+            && self.ac.is_none_or(|ac| inputs.charging == ac)
+            && self
+                .lid_closed
+                .is_none_or(|closed| (inputs.lid == LidState::Closed) == closed)
+            && self.usage_above.is_none_or(|u| inputs.usage > u)
+            && self.usage_below.is_none_or(|u| inputs.usage < u)
+            && self.temp_above.is_none_or(|t| inputs.temp > t)
+            && self.temp_below.is_none_or(|t| inputs.temp < t)
+            && time_between(inputs.time, after, before)
+            && self
+                .days
+                .as_ref()
+                .is_none_or(|days| parse_days(days).is_ok_and(|days| days.contains(&day)))
+            && (!self.has_process_conditions()
+                || self.matching_process(&inputs.processes).is_some())
+    }
+
+    pub fn has_process_conditions(&self) -> bool {
+        self.processes.is_some() || self.cmdline.is_some() || self.cgroup.is_some()
+    }
+
+    /// The first process that meets every process condition on its own
+    pub fn matching_process<'a>(&self, processes: &'a [ProcessInfo]) -> Option<&'a ProcessInfo> {
+        let cmdline = match &self.cmdline {
+            Some(pattern) => Some(compile_pattern(pattern.clone()).ok()?),
+            None => None,
+        };
+
+        processes.iter().find(|process| {
+            self.processes
+                .as_ref()
+                .is_none_or(|names| names.contains(&process.name))
+                && cmdline
+                    .as_ref()
+                    .is_none_or(|regex| regex.is_match(&process.cmdline))
+                && self
+                    .cgroup
+                    .as_ref()
+                    .is_none_or(|cgroup| process.cgroup.contains(cgroup.as_str()))
+        })
+    }
+}
+
+impl Rule {
+    /// Check the parts of a rule that do not depend on the hardware
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.name.is_empty() {
+            return Err(Error::InvalidValue("every rule needs a name".to_string()));
+        }
+
+        for time in [&self.conditions.after, &self.conditions.before]
+            .into_iter()
+            .flatten()
+        {
+            parse_time(time)?;
+        }
+
+        if let Some(days) = &self.conditions.days {
+            parse_days(days)?;
+        }
+
+        if let Some(pattern) = &self.conditions.cmdline {
+            compile_pattern(pattern.clone()).map_err(|e| {
+                Error::InvalidValue(format!(
+                    "cmdline of rule {} is not a regex: {}",
+                    self.name, e
+                ))
+            })?;
+        }
+
+        for percent in [self.conditions.battery_below, self.conditions.battery_above]
+            .into_iter()
+            .flatten()
+        {
+            if !(0..=100).contains(&percent) {
+                return Err(Error::InvalidValue(format!(
+                    "battery percent {} of rule {} is not between 0 and 100",
+                    percent, self.name
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Picks which user rule applies, keeping track of how long each has been matching
+#[derive(Debug, Default)]
+pub struct RuleEngine {
+    /// Sorted from the highest priority to the lowest
+    rules: Vec<Rule>,
+    /// When each rule started matching, None while it does not match
+    matching_since: Vec<Option<SystemTime>>,
+    /// The process that made the rule that applies match, e.g. cargo (1234)
+    trigger: Option<String>,
+}
+
+impl RuleEngine {
+    pub fn new(mut rules: Vec<Rule>) -> RuleEngine {
+        // A stable sort keeps rules with the same priority in the order they were written
+        rules.sort_by_key(|rule| std::cmp::Reverse(rule.priority));
+        RuleEngine {
+            matching_since: vec![None; rules.len()],
+            rules,
+            trigger: None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Rule> {
+        self.rules.iter().find(|rule| rule.name == name)
+    }
+
+    /// If any rule has a process condition, reading every process is skipped otherwise
+    pub fn needs_processes(&self) -> bool {
+        self.rules
+            .iter()
+            .any(|rule| rule.conditions.has_process_conditions())
+    }
+
+    pub fn needs_cmdline(&self) -> bool {
+        self.rules
+            .iter()
+            .any(|rule| rule.conditions.cmdline.is_some())
+    }
+
+    pub fn needs_cgroup(&self) -> bool {
+        self.rules
+            .iter()
+            .any(|rule| rule.conditions.cgroup.is_some())
+    }
+
+    /// The process that made the rule that applies match, if it has process conditions
+    pub fn trigger(&self) -> Option<&str> {
+        self.trigger.as_deref()
+    }
+
+    /// Each rule in priority order, with None when it does not match and otherwise the seconds
+    /// left until it has matched for long enough
+    pub fn remaining_secs(&self, now: SystemTime) -> Vec<(&Rule, Option<u64>)> {
+        self.rules
+            .iter()
+            .zip(&self.matching_since)
+            .map(|(rule, since)| {
+                let remaining = since.map(|since| {
+                    let held = now.duration_since(since).unwrap_or_default().as_secs();
+                    rule.conditions.for_secs.unwrap_or(0).saturating_sub(held)
+                });
+                (rule, remaining)
+            })
+            .collect()
+    }
+
+    /// The highest priority rule whose conditions have held for long enough
+    pub fn evaluate(&mut self, inputs: &RuleInputs, now: SystemTime) -> Option<&Rule> {
+        let mut found = None;
+
+        // Every rule is checked so that the time each one started matching stays correct
+        for (i, rule) in self.rules.iter().enumerate() {
+            if !rule.conditions.matches(inputs) {
+                self.matching_since[i] = None;
+                continue;
+            }
+
+            let since = *self.matching_since[i].get_or_insert(now);
+            let held = now.duration_since(since).unwrap_or_default().as_secs();
+            if found.is_none() && held >= rule.conditions.for_secs.unwrap_or(0) {
+                found = Some(i);
+            }
+        }
+
+        self.trigger = found.and_then(|i| {
+            self.rules[i]
+                .conditions
+                .matching_process(&inputs.processes)
+                .map(|process| format!("{} ({})", process.name, process.pid))
+        });
+
+        found.map(|i| &self.rules[i])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn make_inputs() -> RuleInputs {
+        RuleInputs {
+            charge: 50,
+            charging: false,
+            lid: LidState::Open,
+            usage: 80.0,
+            temp: 60,
+            time: NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+            weekday: Weekday::Wed,
+            processes: vec![ProcessInfo {
+                pid: 1234,
+                name: "firefox".to_string(),
+                cmdline: "/usr/lib/firefox/firefox -P work".to_string(),
+                cgroup: "0::/user.slice/user-1000.slice/app-firefox.scope".to_string(),
+            }],
+        }
+    }
+
+    fn make_rule(name: &str, priority: i32, conditions: Conditions) -> Rule {
+        Rule {
+            name: name.to_string(),
+            priority,
+            conditions,
+            actions: Action::default(),
+        }
+    }
+
+    #[test]
+    fn conditions_match_unit_test() {
+        let inputs = make_inputs();
+
+        assert!(Conditions::default().matches(&inputs));
+        assert!(Conditions {
+            ac: Some(false),
+            usage_above: Some(70.0),
+            battery_below: Some(60),
+            processes: Some(vec!["firefox".to_string()]),
+            ..Default::default()
+        }
+        .matches(&inputs));
+        assert!(!Conditions {
+            ac: Some(true),
+            ..Default::default()
+        }
+        .matches(&inputs));
+        assert!(!Conditions {
+            lid_closed: Some(true),
+            ..Default::default()
+        }
+        .matches(&inputs));
+        assert!(!Conditions {
+            processes: Some(vec!["blender".to_string()]),
+            ..Default::default()
+        }
+        .matches(&inputs));
+
+        // A window that wraps around midnight
+        let night = Conditions {
+            after: Some("22:00".to_string()),
+            before: Some("07:00".to_string()),
+            ..Default::default()
+        };
+        assert!(!night.matches(&inputs));
+        let late = RuleInputs {
+            time: NaiveTime::from_hms_opt(23, 30, 0).unwrap(),
+            ..make_inputs()
+        };
+        assert!(night.matches(&late));
+    }
+
+    #[test]
+    fn process_conditions_unit_test() {
+        let mut inputs = make_inputs();
+        inputs.processes.push(ProcessInfo {
+            pid: 2000,
+            name: "cargo".to_string(),
+            cmdline: "cargo build --release".to_string(),
+            cgroup: "0::/user.slice/user-1000.slice/session-2.scope".to_string(),
+        });
+
+        let build = Conditions {
+            processes: Some(vec![
+                "cargo".to_string(),
+                "rustc".to_string(),
+                "ffmpeg".to_string(),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            build.matching_process(&inputs.processes).map(|p| p.pid),
+            Some(2000)
+        );
+
+        let by_cmdline = Conditions {
+            cmdline: Some("-P (work|school)".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            by_cmdline
+                .matching_process(&inputs.processes)
+                .map(|p| p.pid),
+            Some(1234)
+        );
+
+        let by_cgroup = Conditions {
+            cgroup: Some("app-firefox".to_string()),
+            ..Default::default()
+        };
+        assert!(by_cgroup.matches(&inputs));
+
+        // Every process condition has to hold for the same process
+        let mixed = Conditions {
+            processes: Some(vec!["cargo".to_string()]),
+            cgroup: Some("app-firefox".to_string()),
+            ..Default::default()
+        };
+        assert!(!mixed.matches(&inputs));
+
+        // The engine reports which process made the rule apply
+        let mut engine = RuleEngine::new(vec![make_rule("build", 0, build)]);
+        assert!(engine.evaluate(&inputs, SystemTime::UNIX_EPOCH).is_some());
+        assert_eq!(engine.trigger(), Some("cargo (2000)"));
+
+        inputs.processes.truncate(1);
+        assert!(engine.evaluate(&inputs, SystemTime::UNIX_EPOCH).is_none());
+        assert_eq!(engine.trigger(), None);
+    }
+
+    #[test]
+    fn schedule_unit_test() {
+        let working_hours = Conditions {
+            days: Some(vec!["weekdays".to_string()]),
+            after: Some("09:00".to_string()),
+            before: Some("18:00".to_string()),
+            ..Default::default()
+        };
+        let at = |weekday, hour| RuleInputs {
+            time: NaiveTime::from_hms_opt(hour, 0, 0).unwrap(),
+            weekday,
+            ..make_inputs()
+        };
+
+        assert!(working_hours.matches(&at(Weekday::Wed, 12)));
+        assert!(!working_hours.matches(&at(Weekday::Wed, 20)));
+        assert!(!working_hours.matches(&at(Weekday::Sat, 12)));
+
+        // Friday night carries on into saturday morning, but sunday night does not
+        let weekday_nights = Conditions {
+            days: Some(vec!["mon".to_string(), "Friday".to_string()]),
+            after: Some("22:00".to_string()),
+            before: Some("06:00".to_string()),
+            ..Default::default()
+        };
+        assert!(weekday_nights.matches(&at(Weekday::Fri, 23)));
+        assert!(weekday_nights.matches(&at(Weekday::Sat, 2)));
+        assert!(!weekday_nights.matches(&at(Weekday::Sat, 23)));
+        assert!(!weekday_nights.matches(&at(Weekday::Fri, 2)));
+        assert!(weekday_nights.matches(&at(Weekday::Tue, 2)));
+
+        let weekends = Conditions {
+            days: Some(vec!["weekends".to_string()]),
+            ..Default::default()
+        };
+        assert!(weekends.matches(&at(Weekday::Sun, 12)));
+        assert!(!weekends.matches(&at(Weekday::Mon, 12)));
+
+        // A time that can not be read never matches instead of matching all day
+        let broken = Conditions {
+            after: Some("25:00".to_string()),
+            ..Default::default()
+        };
+        assert!(!broken.matches(&at(Weekday::Wed, 12)));
+    }
+
+    #[test]
+    fn rule_engine_unit_test() {
+        let mut engine = RuleEngine::new(vec![
+            make_rule(
+                "busy",
+                0,
+                Conditions {
+                    usage_above: Some(70.0),
+                    for_secs: Some(30),
+                    ..Default::default()
+                },
+            ),
+            make_rule(
+                "hot",
+                10,
+                Conditions {
+                    temp_above: Some(90),
+                    ..Default::default()
+                },
+            ),
+        ]);
+        let start = SystemTime::UNIX_EPOCH;
+        let mut inputs = make_inputs();
+
+        // Busy has not been matching for long enough
+        assert_eq!(engine.evaluate(&inputs, start), None);
+        let rule = engine.evaluate(&inputs, start + Duration::from_secs(30));
+        assert_eq!(rule.map(|r| r.name.as_str()), Some("busy"));
+
+        // The higher priority rule wins
+        inputs.temp = 95;
+        let rule = engine.evaluate(&inputs, start + Duration::from_secs(31));
+        assert_eq!(rule.map(|r| r.name.as_str()), Some("hot"));
+
+        // Dropping below the usage restarts the time busy needs
+        inputs.temp = 60;
+        inputs.usage = 10.0;
+        assert_eq!(
+            engine.evaluate(&inputs, start + Duration::from_secs(32)),
+            None
+        );
+        inputs.usage = 80.0;
+        assert_eq!(
+            engine.evaluate(&inputs, start + Duration::from_secs(33)),
+            None
+        );
+
+        // Hot goes first as it has the higher priority
+        let remaining: Vec<Option<u64>> = engine
+            .remaining_secs(start + Duration::from_secs(40))
+            .into_iter()
+            .map(|(_, remaining)| remaining)
+            .collect();
+        assert_eq!(remaining, vec![None, Some(23)]);
+    }
+
+    #[test]
+    fn validate_action_unit_test() {
+        let capabilities = Capabilities {
+            governors: vec!["performance".to_string(), "powersave".to_string()],
+            epps: vec!["balance_power".to_string()],
+            freq_range: Some((400_000, 4_000_000)),
+            turbo: true,
+            platform_profiles: Vec::new(),
+        };
+
+        assert!(Action::default().validate(&capabilities).is_ok());
+        assert!(Action {
+            governor: Some("powersave".to_string()),
+            epp: Some("balance_power".to_string()),
+            max_freq: Some(2_000_000),
+            turbo: Some(false),
+            platform_profile: None,
+        }
+        .validate(&capabilities)
+        .is_ok());
+
+        // Not available on this cpu
+        assert!(Action {
+            governor: Some("schedutil".to_string()),
+            ..Default::default()
+        }
+        .validate(&capabilities)
+        .is_err());
+        // Not a governor acs can set
+        assert!(Action {
+            governor: Some("ondemand".to_string()),
+            ..Default::default()
+        }
+        .validate(&capabilities)
+        .is_err());
+        assert!(Action {
+            max_freq: Some(5_000_000),
+            ..Default::default()
+        }
+        .validate(&capabilities)
+        .is_err());
+        assert!(Action {
+            platform_profile: Some("quiet".to_string()),
+            ..Default::default()
+        }
+        .validate(&capabilities)
+        .is_err());
+    }
+
+    #[test]
+    fn validate_rule_unit_test() {
+        assert!(make_rule("ok", 0, Conditions::default()).validate().is_ok());
+        assert!(make_rule("", 0, Conditions::default()).validate().is_err());
+        assert!(make_rule(
+            "bad_time",
+            0,
+            Conditions {
+                after: Some("25:00".to_string()),
+                ..Default::default()
+            }
+        )
+        .validate()
+        .is_err());
+        assert!(make_rule(
+            "bad_day",
+            0,
+            Conditions {
+                days: Some(vec!["someday".to_string()]),
+                ..Default::default()
+            }
+        )
+        .validate()
+        .is_err());
+        assert!(make_rule(
+            "bad_regex",
+            0,
+            Conditions {
+                cmdline: Some("(unclosed".to_string()),
+                ..Default::default()
+            }
+        )
+        .validate()
+        .is_err());
+        assert!(make_rule(
+            "bad_percent",
+            0,
+            Conditions {
+                battery_below: Some(120),
+                ..Default::default()
+            }
+        )
+        .validate()
+        .is_err());
+    }
+}
@@ -15,6 +15,8 @@ pub struct Settings {
     pub delay_battery: u64,
     /// If the daemon should edit the governor based on the rules
     pub edit: bool,
+    /// Run edit mode without writing to sysfs, every write is logged instead
+    pub dry_run: bool,
     /// If the daemon should hook on to another client daemon
     pub hook: bool,
     /// If ACS should show an ASCII animation when turbo is enabled
@@ -43,6 +45,7 @@ impl Default for Settings {
             delay: 1000,
             delay_battery: 1000,
             edit: false,
+            dry_run: false,
             hook: false,
             animation: true,
             graph: GraphType::default(),
@@ -64,6 +67,7 @@ impl DefaultTesting for Settings {
             delay: 1,
             delay_battery: 2,
             edit: false,
+            dry_run: false,
             hook: false,
             animation: true,
             graph: GraphType::Hidden,
@@ -1,9 +1,62 @@
 #![forbid(unsafe_code)]
 use crate::error::Error;
 use globset::Glob;
+use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// When set, write only records what it would have written, for acs run --dry-run
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+static SKIPPED_WRITES: Mutex<Vec<SkippedWrite>> = Mutex::new(Vec::new());
+
+/// A write that dry run kept from happening
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SkippedWrite {
+    pub path: PathBuf,
+    /// What the file contains now, None when it can not be read
+    pub old: Option<String>,
+    pub new: String,
+}
+
+impl SkippedWrite {
+    fn new(path: &Path, value: &str) -> SkippedWrite {
+        SkippedWrite {
+            path: path.to_path_buf(),
+            old: fs::read_to_string(path)
+                .ok()
+                .map(|old| old.trim_end().to_string()),
+            new: value.trim_end().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for SkippedWrite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "would write {} to {} (currently {})",
+            self.new,
+            self.path.display(),
+            self.old.as_deref().unwrap_or("unreadable")
+        )
+    }
+}
+
+pub fn set_dry_run(enabled: bool) {
+    DRY_RUN.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+/// The writes dry run skipped since this was last called
+pub fn take_skipped_writes() -> Vec<SkippedWrite> {
+    std::mem::take(&mut *SKIPPED_WRITES.lock().unwrap())
+}
 
 pub fn read<T>(val: &mut T, path: &Path) -> Result<(), Error>
 where
@@ -12,7 +65,9 @@ where
 {
     let mut file_content = fs::read_to_string(path)?;
     // Remove the \n char
-    file_content.pop();
+    if file_content.ends_with('\n') {
+        file_content.pop();
+    }
 
     // Convert String to expected value
     *val = file_content.parse::<T>().map_err(|_| Error::Parse)?;
@@ -20,6 +75,20 @@ where
     Ok(())
 }
 
+/// Every write to sysfs goes through here, so that dry run can skip all of them
+pub fn write(path: &Path, value: &str) -> Result<(), Error> {
+    if is_dry_run() {
+        SKIPPED_WRITES
+            .lock()
+            .unwrap()
+            .push(SkippedWrite::new(path, value));
+        return Ok(());
+    }
+
+    fs::write(path, value)?;
+    Ok(())
+}
+
 // Lookup a Hdw based on its Parent Path & a glob
 pub fn get_path_by_glob(sysfs_parent_path: &str, hdw_glob: &str) -> Result<PathBuf, Error> {
     let mut glob_path = sysfs_parent_path.to_string();
@@ -39,3 +108,55 @@ pub fn get_path_by_glob(sysfs_parent_path: &str, hdw_glob: &str) -> Result<PathB
     }
     Err(Error::HdwNotFound)
 }
+
+// Lookup every Hdw based on its Parent Path & a glob, sorted by path
+pub fn get_paths_by_glob(sysfs_parent_path: &str, hdw_glob: &str) -> Result<Vec<PathBuf>, Error> {
+    let mut glob_path = sysfs_parent_path.to_string();
+    glob_path.push_str(hdw_glob);
+
+    let glob = Glob::new(&glob_path)
+        .map_err(|_| Error::Unknown)?
+        .compile_matcher();
+    let entries = fs::read_dir(sysfs_parent_path)?;
+
+    let mut paths = Vec::<PathBuf>::new();
+    for entry in entries {
+        let entry = entry.map_err(|_| Error::Unknown)?;
+        let pathbuf = entry.path();
+        if glob.is_match(&pathbuf) {
+            paths.push(pathbuf);
+        }
+    }
+
+    if paths.is_empty() {
+        return Err(Error::HdwNotFound);
+    }
+
+    paths.sort();
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skipped_write_unit_test() -> Result<(), Error> {
+        let path = std::env::temp_dir().join("acs_skipped_write_unit_test");
+        fs::write(&path, "performance\n")?;
+
+        let skipped = SkippedWrite::new(&path, "powersave");
+        assert_eq!(skipped.old.as_deref(), Some("performance"));
+        assert_eq!(
+            skipped.to_string(),
+            format!(
+                "would write powersave to {} (currently performance)",
+                path.display()
+            )
+        );
+
+        let missing = SkippedWrite::new(&path.join("missing"), "1");
+        assert_eq!(missing.old, None);
+        Ok(())
+    }
+}
@@ -11,6 +11,7 @@ use log::debug;
 
 use crate::cpu::CPU;
 use crate::error::Error;
+use crate::sysfs;
 
 /// Find the average frequency of all cores
 ///
@@ -159,6 +160,74 @@ pub fn check_turbo_enabled() -> Result<bool, Error> {
     Ok(is_turbo)
 }
 
+const NO_TURBO_PATH: &str = "/sys/devices/system/cpu/intel_pstate/no_turbo";
+const BOOST_PATH: &str = "/sys/devices/system/cpu/cpufreq/boost";
+
+/// Check if turbo can be turned on and off
+pub fn can_set_turbo() -> bool {
+    Path::new(NO_TURBO_PATH).exists() || Path::new(BOOST_PATH).exists()
+}
+
+/// Turn turbo on or off, using intel_pstate when it exists and cpufreq boost otherwise
+pub fn set_turbo_enabled(enabled: bool) -> Result<(), Error> {
+    let intel_path = Path::new(NO_TURBO_PATH);
+    let boost_path = Path::new(BOOST_PATH);
+
+    if intel_path.exists() {
+        // no_turbo is the opposite of enabled
+        sysfs::write(intel_path, if enabled { "0" } else { "1" })
+    } else if boost_path.exists() {
+        sysfs::write(boost_path, if enabled { "1" } else { "0" })
+    } else {
+        Err(Error::HdwNotFound)
+    }
+}
+
+/// Read the energy performance preference of the first cpu
+pub fn read_epp() -> Result<String, Error> {
+    read_str("/sys/devices/system/cpu/cpu0/cpufreq/energy_performance_preference")
+}
+
+/// Check the energy performance preferences available for the cpu
+pub fn check_available_epps() -> Result<Vec<String>, Error> {
+    Ok(
+        read_str("/sys/devices/system/cpu/cpu0/cpufreq/energy_performance_available_preferences")?
+            .split_whitespace()
+            .map(|epp| epp.to_string())
+            .collect(),
+    )
+}
+
+/// Set the energy performance preference of every cpu
+pub fn set_epp(cpus: &[CPU], epp: &str) -> Result<(), Error> {
+    for cpu in cpus {
+        sysfs::write(
+            Path::new(&format!(
+                "/sys/devices/system/cpu/{}/cpufreq/energy_performance_preference",
+                cpu.name
+            )),
+            epp,
+        )?;
+    }
+    Ok(())
+}
+
+/// The highest frequency a cpu supports in kHz, scaling_max_freq can only be set up to this
+pub fn read_cpuinfo_max_freq(cpu: &CPU) -> Result<i32, Error> {
+    read_int(&format!(
+        "/sys/devices/system/cpu/{}/cpufreq/cpuinfo_max_freq",
+        cpu.name
+    ))
+}
+
+/// The lowest frequency a cpu supports in kHz
+pub fn read_cpuinfo_min_freq(cpu: &CPU) -> Result<i32, Error> {
+    read_int(&format!(
+        "/sys/devices/system/cpu/{}/cpufreq/cpuinfo_min_freq",
+        cpu.name
+    ))
+}
+
 fn read_govs_file() -> Result<String, Error> {
     let governors_path: &str = "/sys/devices/system/cpu/cpu0/cpufreq/scaling_available_governors";
     let governors_string = fs::read_to_string(governors_path)?;